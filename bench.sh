@@ -1 +1 @@
-cargo run --bin benchmark --release
\ No newline at end of file
+cargo run --bin benchmark --release -- "$@"
\ No newline at end of file
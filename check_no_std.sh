@@ -0,0 +1,4 @@
+cargo build --no-default-features && \
+cargo build --no-default-features --features alloc && \
+cargo build --manifest-path no_std_check/Cargo.toml && \
+cargo test --no-default-features --doc
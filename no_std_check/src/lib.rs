@@ -0,0 +1,40 @@
+//! A `#![no_std]` crate that uses swarm_pool with only the `alloc` feature enabled.
+//! If swarm_pool, or this crate, needs anything from `std`, this crate fails to build.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use swarm_pool::Swarm;
+use swarm_pool::static_swarm::StaticSwarm;
+
+#[derive(Default, Clone)]
+pub struct Particle {
+    pub x: f32,
+    pub ttl: u8,
+}
+
+/// Uses the heap Swarm, which needs `alloc`
+pub fn heap_swarm() -> usize {
+    let mut swarm = Swarm::<Particle, u32>::new(16, 0);
+    let spawns: Vec<_> = (0..4).map(|_| swarm.spawn().unwrap()).collect();
+
+    swarm.for_each(|p| p.x += 1.0);
+    swarm.update(|ctl| {
+        *ctl.properties += 1;
+        if ctl.target().ttl == 0 { ctl.kill_current(); }
+    });
+    swarm.kill(&spawns[0]);
+    swarm.count()
+}
+
+/// Uses the StaticSwarm, which needs neither `std` nor `alloc`
+pub fn static_swarm() -> usize {
+    let mut swarm = StaticSwarm::<Particle, (), 16>::new(());
+    let spawn = swarm.spawn().unwrap();
+
+    swarm.for_each(|p| p.x += 1.0);
+    swarm.kill(&spawn);
+    swarm.count()
+}
@@ -1,12 +1,29 @@
 
-#[allow(unused_imports)]
 use std::cmp::Ordering;
 use std::io::Write;
+use std::time::{ Duration, Instant };
+use std::{ env, fs, process };
 
 extern crate swarm_pool;
-use swarm_pool::Swarm;
-//use swarm_pool::tools::sized_pool::SizedPool1024;
-//use swarm_pool::tools::sized_pool;
+use swarm_pool::{ Swarm, Spawn };
+
+const USAGE: &str = "\
+Usage: benchmark [options]
+
+Options:
+  --objects <list>      comma separated object counts to bench with (default: 1,10,100,1000)
+  --duration <ms>       how long every single bench runs, in milliseconds (default: 1000)
+  --loops <list>        comma separated loops to bench (default: all)
+                        available: vec, for_each, for_all, update,
+                        vec_churn, churn, clone_heavy_churn, heavy_churn,
+                        vec_fetch, fetch, vec_kill_loop, update_kill
+  --format <format>     output format: text, csv or json (default: text)
+  --baseline <file>     compare the results against a file written by --format csv or json
+  --threshold <pct>     the slowdown in percent that counts as a regression (default: 5)
+  --help                print this message
+
+With --baseline the percentage change of every bench is reported, and the benchmark
+exits with status 1 when any bench is slower than the baseline by more than the threshold.";
 
 // test mockup objects
 
@@ -35,275 +52,660 @@ impl Minion {
     }
 }
 
+/// A heap owning pool object of about 300 bytes, like the entities of a game
+#[derive(Clone)]
+pub struct Entity {
+    stats: [u64; 32],
+    path: Vec<u32>,
+    inventory: Vec<u32>,
+}
+
+impl Entity {
+    fn new() -> Self {
+        Entity { stats: [0; 32], path: (0..16).collect(), inventory: (0..8).collect() }
+    }
+
+    /// Reset a recycled entity, it gets a path and inventory like a newly spawned entity
+    fn reset(&mut self) {
+        self.stats = [0; 32];
+        self.path.clear();
+        self.path.extend(0..16);
+        self.inventory.clear();
+        self.inventory.extend(0..8);
+    }
+}
+
 // bench test settings
 
-const NUM_SAMPLES: u128 = 2_000_000_000;
+/// A bench runs a loop with a number of objects and returns the number of calls per second
+type Bencher = fn(usize, &Settings) -> f64;
+
+/// All loops that can be benched as (name, description, baseline, bencher), in the order they
+/// are run. Swarm loops are compared with the plain Vec loop named as their baseline.
+const LOOPS: &[(&str, &str, Option<&str>, Bencher)] = &[
+    ("vec", "Standard Vec", None, vec_heap_bencher),
+    ("for_each", "Swarm.for_each()", Some("vec"), for_heap_bencher),
+    ("for_all", "Swarm.for_all()", Some("vec"), forall_heap_bencher),
+    ("update", "Swarm.update()", Some("vec"), update_heap_bencher),
+    ("vec_churn", "Vec swap_remove() & push() churn", None, vec_churn_bencher),
+    ("churn", "Swarm.kill() & spawn() churn", Some("vec_churn"), churn_bencher),
+    ("clone_heavy_churn", "Clone swapping kill & spawn() churn of entities", None, clone_heavy_churn_bencher),
+    ("heavy_churn", "Swarm.kill() & spawn() churn of entities", Some("clone_heavy_churn"), heavy_churn_bencher),
+    ("vec_fetch", "Vec random index", None, vec_fetch_bencher),
+    ("fetch", "Swarm.fetch() random spawn", Some("vec_fetch"), fetch_bencher),
+    ("vec_kill_loop", "Vec swap_remove() in loop", None, vec_kill_loop_bencher),
+    ("update_kill", "Swarm.kill_current() in update()", Some("vec_kill_loop"), update_kill_bencher),
+];
+
+#[derive(PartialEq)]
+enum Format { Text, Csv, Json }
+
+struct Settings {
+    objects: Vec<usize>,
+    duration: Duration,
+    loops: Vec<&'static str>,
+    format: Format,
+    baseline: Option<String>,
+    threshold: f64,
+}
+
+impl Settings {
+    fn from_args(args: &[String]) -> Result<Settings, String> {
+        let mut settings = Settings {
+            objects: vec![1, 10, 100, 1_000],
+            duration: Duration::from_millis(1_000),
+            loops: LOOPS.iter().map(|l| l.0).collect(),
+            format: Format::Text,
+            baseline: None,
+            threshold: 5.0,
+        };
+
+        let mut i = 0;
+        while i < args.len() {
+            let flag = args[i].as_str();
+            if flag == "--help" || flag == "-h" {
+                return Err(String::new());
+            }
+            let value = args.get(i + 1).ok_or(format!("missing value for '{}'", flag))?;
+
+            match flag {
+                "--objects" => {
+                    settings.objects = value.split(',')
+                        .map(|v| v.trim().parse::<usize>().ok().filter(|n| *n > 0))
+                        .collect::<Option<Vec<usize>>>()
+                        .ok_or(format!("invalid object counts '{}'", value))?;
+                },
+                "--duration" => {
+                    let ms = value.parse::<u64>().map_err(|_| format!("invalid duration '{}'", value))?;
+                    settings.duration = Duration::from_millis(ms);
+                },
+                "--loops" => {
+                    settings.loops = value.split(',')
+                        .map(|v| LOOPS.iter().map(|l| l.0).find(|name| *name == v.trim()))
+                        .collect::<Option<Vec<&str>>>()
+                        .ok_or(format!("unknown loop in '{}'", value))?;
+                },
+                "--format" => {
+                    settings.format = match value.as_str() {
+                        "text" => Format::Text,
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        _ => return Err(format!("unknown format '{}'", value)),
+                    };
+                },
+                "--baseline" => settings.baseline = Some(value.clone()),
+                "--threshold" => {
+                    settings.threshold = value.parse::<f64>().map_err(|_| format!("invalid threshold '{}'", value))?;
+                },
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+            i += 2;
+        }
+        Ok(settings)
+    }
+
+    /// Progress messages go to stderr when stdout is used for machine readable output
+    fn progress(&self, message: &str) {
+        if self.format == Format::Text {
+            print!("{}", message);
+            #[allow(unused_must_use)] { std::io::stdout().flush(); }
+        } else {
+            eprint!("{}", message);
+        }
+    }
+}
+
+/// The result of a single bench run
+struct Record {
+    name: String,
+    objects: usize,
+    speed: f64,
+}
 
 // bench implementation
 
 fn main() {
-    let mut run_id: usize = 0;
-    let (vec_bn1, for_h_bn1, upd_h_bn1, upc_h_bn1) = bench_with_objects(&mut run_id);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let settings = match Settings::from_args(&args) {
+        Ok(settings) => settings,
+        Err(message) => {
+            if message.is_empty() {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
 
-    println!("# RESULTS TOTAL:");
+    let records = bench_with_objects(&settings);
 
-    println!("* Plain vec results:");
-    {
-        println!("  - average of '{}M' calls/s", (vec_bn1.avg() / 1_000_000.0).round());
-        let vmin = vec_bn1.min();
-        println!("  - lowest of '{}M' calls/s (bench #{})", 
-            (vmin.1 / 1_000_000.0).round(), vmin.0);
-        let vmax = vec_bn1.max();
-        println!("  - highest of '{}M' calls/s (becnh #{})", 
-            (vmax.1 / 1_000_000.0).round(), vmax.0);
+    match settings.format {
+        Format::Text => print_results(&records),
+        Format::Csv => print_csv(&records),
+        Format::Json => print_json(&records),
     }
-    print_result(&vec_bn1, &for_h_bn1, "swarm.for_each()"); 
-    print_result(&vec_bn1, &upd_h_bn1, "swarm.for_all()"); 
-    print_result(&vec_bn1, &upc_h_bn1, "swarm.update()"); 
-    //print_result(&vec_bn1, &pool_bench, "pool::for_each()"); 
 
+    if let Some(path) = &settings.baseline {
+        let baseline = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_records(&text));
+
+        match baseline {
+            Ok(baseline) => {
+                if compare_with_baseline(&settings, &records, &baseline) { process::exit(1); }
+            },
+            Err(message) => {
+                eprintln!("error: could not read baseline '{}': {}", path, message);
+                process::exit(2);
+            }
+        }
+    }
 }
 
-fn print_result(baseline: &Bench, test_bench: &Bench, descript: &str) {
-    println!("* {} results:", descript); 
+fn bench_with_objects(settings: &Settings) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut run_id: usize = 0;
+
+    for objects in &settings.objects {
+        std::thread::sleep(Duration::from_millis(500));
+        let mut speeds: Vec<(&str, f64)> = Vec::new();
 
-    println!("  - average of '{}M' calls/s", (test_bench.avg() / 1_000_000.0).round());
-    println!("  - average speed was '{}%' of plain vector speed", 
-        ((test_bench.avg() / baseline.avg()) * 100_000.0).round() / 1_000.0);
+        for (name, descript, baseline, bencher) in LOOPS.iter().filter(|l| settings.loops.contains(&l.0)) {
+            run_id += 1;
+            settings.progress(&format!("{}: {} bench with {} object(s).. ", run_id, descript, objects));
 
-    let vmin = test_bench.min();
-    println!("  - lowest of '{}M' calls/s (becnh #{})", 
-        (vmin.1 / 1_000_000.0).round(), vmin.0);
-    let vmax = test_bench.max();
-    println!("  - highest of '{}M' calls/s (becnh #{})", 
-        (vmax.1 / 1_000_000.0).round(), vmax.0);
-}
+            let speed = bencher(*objects, settings);
+            let m_calls = (speed / 1_000_000.0).round();
 
-fn bench_with_objects(run_id: &mut usize) -> (Bench, Bench, Bench, Bench) { //}, Bench) {
-    
-    let (v_spd1, fh_spd1, uh_spd1, ch_spd1) = bench_with(run_id, 1);
-    let (v_spd2, fh_spd2, uh_spd2, ch_spd2) = bench_with(run_id, 10);
-    let (v_spd3, fh_spd3, uh_spd3, ch_spd3) = bench_with(run_id, 100);
-    let (v_spd4, fh_spd4, uh_spd4, ch_spd4) = bench_with(run_id, 1_000);
-  
-    println!("--");
-    (   
-        Bench (vec![v_spd1, v_spd2, v_spd3, v_spd4]),
-        Bench (vec![fh_spd1, fh_spd2, fh_spd3, fh_spd4]),
-        Bench (vec![uh_spd1, uh_spd2, uh_spd3, uh_spd4]),
-        Bench (vec![ch_spd1, ch_spd2, ch_spd3, ch_spd4]),
-        //Bench (vec![p_spd1, p_spd2, p_spd3, p_spd4]),
-    )
+            match baseline.and_then(|b| speeds.iter().find(|s| s.0 == b)) {
+                Some((_, vec)) => settings.progress(&format!("{}M calls/s({}%) @ {}M upd/s\n",
+                    m_calls, (100.0 * speed / vec).round(), m_calls / *objects as f64)),
+                None => settings.progress(&format!("{}M calls/s @ {}M upd/s\n",
+                    m_calls, m_calls / *objects as f64)),
+            }
+            speeds.push((name, speed));
+
+            records.push(Record { name: name.to_string(), objects: *objects, speed });
+        }
+    }
+    settings.progress("--\n");
+    records
 }
 
 type Speed = (usize, f64);
 
-fn bench_with(run_id: &mut usize, objects: u128) -> (Speed, Speed, Speed, Speed) {
-    let fn_avg = |x: f64, vec: f64| (100.0 * x / vec).round();
+/// Collects the speeds of one loop over all object counts, as (bench #, speed)
+fn collect_bench(records: &[Record], name: &str) -> Bench {
+    Bench(records.iter()
+        .enumerate()
+        .filter(|(_, r)| r.name == name)
+        .map(|(i, r)| (i + 1, r.speed))
+        .collect())
+}
+
+fn print_results(records: &[Record]) {
+    println!("# RESULTS TOTAL:");
+
+    for (name, descript, baseline, _) in LOOPS {
+        let test_bench = collect_bench(records, name);
+        if test_bench.0.is_empty() { continue; }
+        println!("* {} results:", descript);
 
-    std::thread::sleep(std::time::Duration::from_millis(500));
-    
-    let vec_spd = vec_heap_bencher(run_id, objects);
-    {
-        let m_calls = (vec_spd.1 / 1_000_000.0).round();
-        println!("{}M calls/s @ {}M upd/s", m_calls, m_calls / objects as f64);
+        println!("  - average of '{}M' calls/s", (test_bench.avg() / 1_000_000.0).round());
+        if let Some(baseline) = baseline.map(|b| collect_bench(records, b)).filter(|b| !b.0.is_empty()) {
+            println!("  - average speed was '{}%' of plain vector speed",
+                ((test_bench.avg() / baseline.avg()) * 100_000.0).round() / 1_000.0);
+        }
+
+        let vmin = test_bench.min();
+        println!("  - lowest of '{}M' calls/s (becnh #{})",
+            (vmin.1 / 1_000_000.0).round(), vmin.0);
+        let vmax = test_bench.max();
+        println!("  - highest of '{}M' calls/s (becnh #{})",
+            (vmax.1 / 1_000_000.0).round(), vmax.0);
     }
+}
 
-    let for_h_spd = for_heap_bencher(run_id, objects);
-    {
-        let m_calls = (for_h_spd.1 / 1_000_000.0).round();
-        let avg = fn_avg(for_h_spd.1, vec_spd.1);
-        println!("{}M calls/s({}%) @ {}M upd/s", m_calls, avg, m_calls / objects as f64);
+fn print_csv(records: &[Record]) {
+    println!("name,objects,calls_per_sec");
+    for r in records {
+        println!("{},{},{:.0}", r.name, r.objects, r.speed);
     }
+}
 
-    let upd_h_spd = forall_heap_bencher(run_id, objects);
-    {
-        let m_calls = (upd_h_spd.1 / 1_000_000.0).round();
-        let avg = fn_avg(upd_h_spd.1, vec_spd.1);
-        println!("{}M calls/s({}%) @ {}M upd/s", m_calls, avg, m_calls / objects as f64);
+fn print_json(records: &[Record]) {
+    println!("{{");
+    println!("  \"benchmarks\": [");
+    for (i, r) in records.iter().enumerate() {
+        let comma = if i + 1 < records.len() { "," } else { "" };
+        println!("    {{ \"name\": \"{}\", \"objects\": {}, \"calls_per_sec\": {:.0} }}{}",
+            r.name, r.objects, r.speed, comma);
     }
-    let uct_h_spd = update_heap_bencher(run_id, objects);
-    {
-        let m_calls = (uct_h_spd.1 / 1_000_000.0).round();
-        let avg = fn_avg(uct_h_spd.1, vec_spd.1);
-        println!("{}M calls/s({}%) @ {}M upd/s", m_calls, avg, m_calls / objects as f64);
-    } 
-    // let pool_spd = sized_pool_bencher(run_id, objects);
-    // {
-    //     let m_calls = (pool_spd.1 / 1_000_000.0).round();
-    //     let avg = fn_avg(pool_spd.1, vec_spd.1);
-    //     println!("{}M calls/s({}%) @ {}M upd/s", m_calls, avg, m_calls / objects as f64);
-    // }
-    
-
-    (vec_spd, for_h_spd, upd_h_spd, uct_h_spd) //, pool_spd)
+    println!("  ]");
+    println!("}}");
+}
+
+/// Prints the percentage change of every bench compared to the baseline,
+/// returns true if any bench regressed by more than the threshold.
+fn compare_with_baseline(settings: &Settings, records: &[Record], baseline: &[Record]) -> bool {
+    let mut regressed = false;
+    let report = |line: String| match settings.format {
+        Format::Text => println!("{}", line),
+        _ => eprintln!("{}", line),
+    };
+    report(format!("# COMPARED TO BASELINE (threshold {}%):", settings.threshold));
+
+    for r in records {
+        match baseline.iter().find(|b| b.name == r.name && b.objects == r.objects) {
+            Some(b) if b.speed > 0.0 => {
+                let change = (r.speed - b.speed) / b.speed * 100.0;
+                let failed = change < -settings.threshold;
+                regressed |= failed;
+
+                report(format!("* {} with {} object(s): {:+.2}%{}",
+                    r.name, r.objects, change, if failed { " REGRESSION" } else { "" }));
+            },
+            _ => report(format!("* {} with {} object(s): not in baseline", r.name, r.objects)),
+        }
+    }
+    regressed
 }
 
 struct Bench(Vec<Speed>);
 
 impl Bench {
-    // pub fn merge(mut self, other: Bench) -> Self {
-    //     for v in other.0 {
-    //         self.0.push(v);
-    //     }
-    //     self
-    // }
-
     pub fn avg(&self) -> f64 { self.0.iter().map(|x| x.1).sum::<f64>() / self.0.len() as f64 }
 
-    pub fn min(&self) -> Speed { 
+    pub fn min(&self) -> Speed {
         let min = *self.0.iter().max_by(|a, b| cmp(&a.1, &b.1)).unwrap();
         min
     }
 
-    pub fn max(&self) -> Speed { 
+    pub fn max(&self) -> Speed {
         let max = *self.0.iter().min_by(|a, b| cmp(&a.1, &b.1)).unwrap();
         max
     }
 }
 
-pub fn cmp(a:&f64, b:&f64) -> Ordering { 
+pub fn cmp(a:&f64, b:&f64) -> Ordering {
     if a < b { Ordering::Greater } else { Ordering::Less }
 }
 
-fn vec_heap_bencher(id: &mut usize, amount: u128) -> Speed {
-    *id += 1;
-    print!("{}: Standard Vec bench for {} object(s).. ",id, amount);
-    #[allow(unused_must_use)] { std::io::stdout().flush(); }
+/// Calls `frame` over and over until the bench duration has passed.
+/// Returns the number of frames and the elapsed time in seconds.
+fn run_for<Frame: FnMut()>(settings: &Settings, objects: usize, mut frame: Frame) -> (u128, f64) {
+    let batch = (1_000_000 / objects).max(1);
+    let mut frames = 0;
+
+    let now = Instant::now();
+    loop {
+        for _j in 0..batch { frame(); }
+        frames += batch as u128;
+        if now.elapsed() >= settings.duration { break; }
+    }
+    (frames, now.elapsed().as_secs_f64())
+}
 
+fn vec_heap_bencher(amount: usize, settings: &Settings) -> f64 {
     // get 'standard vector' thread speed
-    let mut vec_test = vec![Minion::default(); amount as usize];
+    let mut vec_test = vec![Minion::default(); amount];
 
-    let now = std::time::SystemTime::now();
-    for _j in 0..NUM_SAMPLES/amount { 
-        for k in 0..amount as usize {
-            vec_test[k].calls += 1;
+    let (frames, time) = run_for(settings, amount, || {
+        for minion in vec_test.iter_mut() {
+            minion.calls += 1;
         }
-    }
-    let elapsed_vec = now.elapsed();
+    });
 
     // base test results
-    let time = elapsed_vec.unwrap().as_secs_f64();
-    let speed = (vec_test[0].calls * amount as u128) as f64 / time;
-    assert_eq!(vec_test[0].calls, NUM_SAMPLES / amount as u128);
-
-    // return result
-    (*id, speed)
+    assert_eq!(vec_test[0].calls, frames);
+    (frames * amount as u128) as f64 / time
 }
 
-fn for_heap_bencher(id: &mut usize, amount: u128) -> Speed {
-    *id += 1;
-    print!("{}: Swarm.for_each() bench with {} object(s).. ", id, amount);
-    #[allow(unused_must_use)] { std::io::stdout().flush(); }
-
+fn for_heap_bencher(amount: usize, settings: &Settings) -> f64 {
     // get swarm ecs system speed
-    let mut swarm = Swarm::<Minion, _>::new(amount as usize, ());
+    let mut swarm = Swarm::<Minion, _>::new(amount, ());
     let s_first = swarm.spawn().unwrap();
     for _e in 1..amount { swarm.spawn(); }
 
     // run bench loop
-    let now = std::time::SystemTime::now();
-    for _j in 0..NUM_SAMPLES/amount { 
+    let (frames, time) = run_for(settings, amount, || {
         swarm.for_each(|obj| {
             obj.calls += 1;
         });
-    }
-    let elapsed_res = now.elapsed();
+    });
 
     // swarm test results
-    let swarm_time = elapsed_res.unwrap().as_secs_f64();
-    let swarm_speed = (swarm.fetch(&s_first).calls * amount as u128) as f64 / swarm_time;
-    assert_eq!(swarm.fetch(&s_first).calls, NUM_SAMPLES / amount);
-
-    (*id, swarm_speed)
+    assert_eq!(swarm.fetch(&s_first).calls, frames);
+    (frames * amount as u128) as f64 / time
 }
 
-fn forall_heap_bencher(id: &mut usize, amount: u128) -> Speed {
-    *id += 1;
-    print!("{}: Swarm.for_all() bench with {} object(s).. ", id, amount);
-    #[allow(unused_must_use)] { std::io::stdout().flush(); }
-    
+fn forall_heap_bencher(amount: usize, settings: &Settings) -> f64 {
     // get swarm ecs system speed
-    let mut swarm = Swarm::<Minion, _>::new(amount as usize, ());
+    let mut swarm = Swarm::<Minion, _>::new(amount, ());
     let s_first = swarm.spawn().unwrap();
 
-    for _e in 1..amount { 
+    for _e in 1..amount {
         let spawn = swarm.spawn().unwrap();
         swarm.fetch(&spawn).summon = Some(Summon::default());
     }
 
     // run bench loop
-    let now = std::time::SystemTime::now();
-    for _j in 0..NUM_SAMPLES/amount { 
+    let (frames, time) = run_for(settings, amount, || {
         swarm.for_all(|index, list, _props| {
             list[*index].calls += 1;
         });
-    }
-    let elapsed_res = now.elapsed();
+    });
 
     // swarm test results
-    let swarm_time = elapsed_res.unwrap().as_secs_f64();
-    let swarm_speed = (swarm.fetch(&s_first).calls * amount as u128) as f64 / swarm_time;
-    assert_eq!(swarm.fetch(&s_first).calls, NUM_SAMPLES / amount);
-
-    (*id, swarm_speed)
+    assert_eq!(swarm.fetch(&s_first).calls, frames);
+    (frames * amount as u128) as f64 / time
 }
 
-fn update_heap_bencher(id: &mut usize, amount: u128) -> Speed {
-    *id += 1;
-    print!("{}: Swarm.update() bench with {} object(s).. ", id, amount);
-    #[allow(unused_must_use)] { std::io::stdout().flush(); }
-
+fn update_heap_bencher(amount: usize, settings: &Settings) -> f64 {
     // get swarm ecs system speed
-    let mut swarm = Swarm::<Minion, _>::new(amount as usize, ());
+    let mut swarm = Swarm::<Minion, _>::new(amount, ());
     let s_first = swarm.spawn().unwrap();
 
-    for _e in 1..amount { 
+    for _e in 1..amount {
         let spawn = swarm.spawn().unwrap();
         swarm.fetch(&spawn).summon = Some(Summon::default());
     }
 
     // run bench loop
-    let now = std::time::SystemTime::now();
-    for _j in 0..NUM_SAMPLES/amount { 
+    let (frames, time) = run_for(settings, amount, || {
         swarm.update(|ctx| {
             ctx.target().calls += 1;
         });
+    });
+
+    // swarm test results
+    assert_eq!(swarm.fetch(&s_first).calls, frames);
+    (frames * amount as u128) as f64 / time
+}
+
+/// The number of objects that is killed and respawned every frame in the churn benches, 10%
+fn churn_amount(amount: usize) -> usize { (amount / 10).max(1) }
+
+fn vec_churn_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get 'standard vector' remove & push speed
+    let mut vec_test = vec![Minion::default(); amount];
+    let churn = churn_amount(amount);
+    let mut rng = Rng::default();
+
+    let (frames, time) = run_for(settings, amount, || {
+        for _k in 0..churn {
+            vec_test.swap_remove(rng.below(vec_test.len()));
+        }
+        for _k in 0..churn {
+            vec_test.push(Minion::default());
+        }
+    });
+
+    // base test results, every frame kills and spawns 'churn' objects
+    assert_eq!(vec_test.len(), amount);
+    (frames * 2 * churn as u128) as f64 / time
+}
+
+fn churn_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get swarm kill & spawn speed
+    let mut swarm = Swarm::<Minion, _>::new(amount, ());
+    for _e in 0..amount { swarm.spawn(); }
+    let churn = churn_amount(amount);
+    let mut rng = Rng::default();
+
+    let (frames, time) = run_for(settings, amount, || {
+        for _k in 0..churn {
+            let target = swarm.fetch_spawn(&rng.below(swarm.count()));
+            swarm.kill(&target);
+        }
+        for _k in 0..churn {
+            let spawn = swarm.spawn().unwrap();
+            *swarm.fetch(&spawn) = Minion::default();
+        }
+    });
+
+    // swarm test results, every frame kills and spawns 'churn' objects
+    assert_eq!(swarm.count(), amount);
+    (frames * 2 * churn as u128) as f64 / time
+}
+
+fn clone_heavy_churn_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get the speed of the previous kill implementation, which swapped the killed object 
+    // with the last spawned object by cloning both, and recycled killed objects
+    let mut pool: Vec<Entity> = (0..amount).map(|_| Entity::new()).collect();
+    let mut len = amount;
+    let churn = churn_amount(amount);
+    let mut rng = Rng::default();
+
+    let (frames, time) = run_for(settings, amount, || {
+        for _k in 0..churn {
+            let target_pos = rng.below(len);
+            let last_pos = len - 1;
+            if target_pos < last_pos {
+                let last = pool[last_pos].clone();
+                let target = pool[target_pos].clone();
+                pool[target_pos] = last;
+                pool[last_pos] = target;
+            }
+            len -= 1;
+        }
+        for _k in 0..churn {
+            pool[len].reset();
+            len += 1;
+        }
+    });
+
+    // base test results, every frame kills and spawns 'churn' objects
+    assert_eq!(len, amount);
+    (frames * 2 * churn as u128) as f64 / time
+}
+
+fn heavy_churn_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get swarm kill & spawn speed for heap owning objects, killed objects are recycled
+    let mut swarm = Swarm::<Entity, _>::new_with(amount, (), Entity::new);
+    for _e in 0..amount { swarm.spawn(); }
+    let churn = churn_amount(amount);
+    let mut rng = Rng::default();
+
+    let (frames, time) = run_for(settings, amount, || {
+        for _k in 0..churn {
+            let target = swarm.fetch_spawn(&rng.below(swarm.count()));
+            swarm.kill(&target);
+        }
+        for _k in 0..churn {
+            let spawn = swarm.spawn().unwrap();
+            swarm.fetch(&spawn).reset();
+        }
+    });
+
+    // swarm test results, every frame kills and spawns 'churn' objects
+    assert_eq!(swarm.count(), amount);
+    (frames * 2 * churn as u128) as f64 / time
+}
+
+fn vec_fetch_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get 'standard vector' random index speed
+    let mut vec_test = vec![Minion::default(); amount];
+    let lookups = Rng::default().indices(amount);
+
+    let (frames, time) = run_for(settings, amount, || {
+        for i in lookups.iter() {
+            vec_test[*i].calls += 1;
+        }
+    });
+
+    // base test results
+    assert_eq!(vec_test.iter().map(|m| m.calls).sum::<u128>(), frames * amount as u128);
+    (frames * amount as u128) as f64 / time
+}
+
+fn fetch_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get swarm fetch by spawn speed
+    let mut swarm = Swarm::<Minion, _>::new(amount, ());
+    let spawns: Vec<Spawn> = (0..amount).map(|_| swarm.spawn().unwrap()).collect();
+    let lookups = Rng::default().indices(amount);
+
+    let (frames, time) = run_for(settings, amount, || {
+        for i in lookups.iter() {
+            swarm.fetch(&spawns[*i]).calls += 1;
+        }
+    });
+
+    // swarm test results
+    assert_eq!(spawns.iter().map(|s| swarm.fetch_ref(s).calls).sum::<u128>(), frames * amount as u128);
+    (frames * amount as u128) as f64 / time
+}
+
+/// Objects die after this many calls in the kill loop benches. Objects start at different
+/// ages, so that about 10% of the objects is killed and respawned every frame.
+const LIFETIME: u128 = 10;
+
+/// Returns true when an object of the kill loop benches has reached the end of its lifetime
+#[allow(clippy::manual_is_multiple_of)] // is_multiple_of() requires Rust 1.87
+fn expired(calls: u128) -> bool { calls % LIFETIME == 0 }
+
+fn vec_kill_loop_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get 'standard vector' swap remove while iterating speed
+    let mut vec_test: Vec<Minion> = (0..amount)
+        .map(|i| Minion { calls: i as u128 % LIFETIME, summon: None })
+        .collect();
+    let mut visits: u128 = 0;
+
+    let (frames, time) = run_for(settings, amount, || {
+        let mut i = 0;
+        while i < vec_test.len() {
+            visits += 1;
+            vec_test[i].calls += 1;
+
+            // swap_remove moves an object that was not visited yet into position i
+            if expired(vec_test[i].calls) { vec_test.swap_remove(i); } else { i += 1; }
+        }
+        while vec_test.len() < amount {
+            vec_test.push(Minion::default());
+        }
+    });
+
+    // base test results
+    assert_eq!(visits, frames * amount as u128);
+    (frames * amount as u128) as f64 / time
+}
+
+fn update_kill_bencher(amount: usize, settings: &Settings) -> f64 {
+    // get swarm kill while updating speed, the properties count the visits
+    let mut swarm = Swarm::<Minion, u128>::new(amount, 0);
+    for i in 0..amount {
+        let spawn = swarm.spawn().unwrap();
+        swarm.fetch(&spawn).calls = i as u128 % LIFETIME;
     }
-    let elapsed_res = now.elapsed();
+
+    let (frames, time) = run_for(settings, amount, || {
+        swarm.update(|ctx| {
+            *ctx.properties += 1;
+            ctx.target().calls += 1;
+            if expired(ctx.target().calls) { ctx.kill_current(); }
+        });
+        while swarm.count() < amount {
+            let spawn = swarm.spawn().unwrap();
+            *swarm.fetch(&spawn) = Minion::default();
+        }
+    });
 
     // swarm test results
-    let swarm_time = elapsed_res.unwrap().as_secs_f64();
-    let swarm_speed = (swarm.fetch(&s_first).calls * amount as u128) as f64 / swarm_time;
-    assert_eq!(swarm.fetch(&s_first).calls, NUM_SAMPLES / amount);
+    assert_eq!(swarm.properties, frames * amount as u128);
+    (frames * amount as u128) as f64 / time
+}
 
-    (*id, swarm_speed)
+/// A small xorshift random number generator, so every bench run uses the same random sequence
+struct Rng(u64);
+
+impl Default for Rng {
+    fn default() -> Self { Rng(0x2545_f491_4f6c_dd1d) }
 }
 
-// fn sized_pool_bencher(id: &mut usize, amount: u128) -> Speed {
-//     *id += 1;
-//     print!("{}: pool::for_each() bench with {} object(s).. ", id, amount);
-//     #[allow(unused_must_use)] { std::io::stdout().flush(); }
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a random number in the range 0..max
+    fn below(&mut self, max: usize) -> usize { (self.next() % max as u64) as usize }
+
+    /// Returns 'amount' random indices in the range 0..amount
+    fn indices(&mut self, amount: usize) -> Vec<usize> {
+        (0..amount).map(|_| self.below(amount)).collect()
+    }
+}
 
-//     // get swarm ecs system speed
-//     let mut pool: SizedPool1024<Summon> = SizedPool1024::new();
+// baseline parsing
 
-//     for _e in 0..amount { 
-//        sized_pool::push(&mut pool, Summon::default());
-//     }
+/// Reads the records from a file that was written with '--format csv' or '--format json'
+fn parse_records(text: &str) -> Result<Vec<Record>, String> {
+    if text.trim_start().starts_with('{') { parse_json(text) } else { parse_csv(text) }
+}
 
-//     // run bench loop
-//     let now = std::time::SystemTime::now();
-//     for _j in 0..NUM_SAMPLES/amount { 
-//         sized_pool::for_each(&mut pool, |obj| obj.calls += 1);
-//     }
-//     let elapsed_res = now.elapsed();
+/// Reads the records from the csv written by print_csv()
+fn parse_csv(csv: &str) -> Result<Vec<Record>, String> {
+    let mut lines = csv.lines().filter(|line| !line.trim().is_empty());
+    if lines.next().map(str::trim) != Some("name,objects,calls_per_sec") {
+        return Err("expected the 'name,objects,calls_per_sec' header".to_string());
+    }
 
-//     // swarm test results
-//     let swarm_time = elapsed_res.unwrap().as_secs_f64();
-//     let num_calls = sized_pool::get_ref(&pool, 0).unwrap().calls;
-//     let swarm_speed = (num_calls * amount as u128) as f64 / swarm_time;
-//     assert_eq!(num_calls, NUM_SAMPLES / amount);
+    lines.enumerate().map(|(i, line)| {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        match fields.as_slice() {
+            [name, objects, speed] => match (objects.parse::<usize>(), speed.parse::<f64>()) {
+                (Ok(objects), Ok(speed)) => Ok(Record { name: name.to_string(), objects, speed }),
+                _ => Err(format!("invalid number on line {}", i + 2)),
+            },
+            _ => Err(format!("expected 3 fields on line {}", i + 2)),
+        }
+    }).collect()
+}
 
-//     (*id, swarm_speed)
-// }
\ No newline at end of file
+/// Reads the records from the json written by print_json(), every bench is an object
+/// with a 'name', 'objects' and 'calls_per_sec' field in the 'benchmarks' list.
+fn parse_json(json: &str) -> Result<Vec<Record>, String> {
+    let list = json.find("\"benchmarks\"")
+        .map(|start| &json[start..])
+        .and_then(|rest| Some(&rest[rest.find('[')? + 1..rest.find(']')?]))
+        .ok_or_else(|| "expected a 'benchmarks' list".to_string())?;
+
+    list.split('}').map(str::trim).filter(|bench| !bench.is_empty()).enumerate().map(|(i, bench)| {
+        let bench = bench.trim_start_matches(',').trim_start().trim_start_matches('{');
+        let field = |key: &str| bench.split(',')
+            .filter_map(|field| field.split_once(':'))
+            .find(|(k, _)| k.trim().trim_matches('"') == key)
+            .map(|(_, value)| value.trim());
+
+        let name = field("name").filter(|n| n.len() >= 2 && n.starts_with('"') && n.ends_with('"'));
+        match (name, field("objects").map(str::parse::<usize>), field("calls_per_sec").map(str::parse::<f64>)) {
+            (Some(name), Some(Ok(objects)), Some(Ok(speed))) =>
+                Ok(Record { name: name[1..name.len() - 1].to_string(), objects, speed }),
+            _ => Err(format!("expected a name, objects and calls_per_sec for bench {}", i + 1)),
+        }
+    }).collect()
+}
@@ -60,7 +60,11 @@
 //!     assert_eq!(swarm.fetch_ref(&s_cristy).value, 2);
 //! ```
 
+#[cfg(not(feature = "std"))]
+use alloc::{ vec, vec::Vec };
+
 use super::types::*;
+use super::record::{ Op, OpLog };
 
 /// SwarmControl is passed as a parameter to the UpdateHandler during the 
 /// swarm.update() loop. The Swarm Control object holds references to swarm pooling
@@ -70,20 +74,29 @@ pub struct SwarmControl<'a, ItemType, Properties> {
     pub(crate) max: &'a usize,
     pub(crate) spawns: &'a mut Vec<Spawn>,
     pub(crate) free: &'a mut Vec<Spawn>,
-    pub(crate) order: &'a mut Vec<usize>,
+    pub(crate) order: &'a mut Vec<SpawnId>,
+    pub(crate) groups: &'a mut Vec<Group>,
+    pub(crate) constructor: ConstructHandler<ItemType>,
+    pub(crate) counters: &'a mut Counters,
+    pub(crate) recorder: &'a mut Option<OpLog>,
+    pub(crate) encoder: Option<EncodeHandler<ItemType>>,
+    pub(crate) ticks: usize,
+    pub(crate) tracking: bool,
 
     pub(crate) len: usize,
     pub(crate) pos: usize, // the pool index of the currently updating spawn
+    pub(crate) previous: &'a [ItemType],   // the pool before a double buffered loop, see Swarm.update_buffered()
+    pub(crate) started: &'a [Option<(ObjectPosition, u32)>], // per spawn id, its position and generation in `previous`
     
     pub pool: &'a mut Vec<ItemType>,
     pub properties: &'a mut Properties,
 }
 
-impl<'a, ItemType, Properties> SwarmControl<'a, ItemType, Properties> 
-where ItemType: Default + Clone {
+impl<'a, ItemType, Properties> SwarmControl<'a, ItemType, Properties> {
 
     /// Returns a mutable reference to the pool object that is currently being updated
     pub fn target(&mut self) -> &mut ItemType {
+        self.mark(&self.spawns[self.pos]);
         &mut self.pool[self.pos]
     }
 
@@ -95,7 +108,7 @@ where ItemType: Default + Clone {
     /// Returns the ObjectPosition, or pool index, where the currently updating pool
     /// object is located at this moment (pool position can change over time).
     pub fn head(&self) -> ObjectPosition {
-        self.order[self.pos]
+        self.pos
     }
 
     /// Returns a spawn reference object from an object position within the pool
@@ -106,6 +119,7 @@ where ItemType: Default + Clone {
     /// Returns a mutable reference to an object from the Swarm pool.
     /// The supplied Spawn reference object points out which object to return.
     pub fn fetch(&mut self, spawn: &Spawn) -> &mut ItemType { 
+        self.mark(spawn);
         &mut self.pool[spawn.0.borrow().pos]
     }
 
@@ -115,10 +129,29 @@ where ItemType: Default + Clone {
         &self.pool[spawn.0.borrow().pos]
     }
 
+    /// Returns the object as it was before the loop started, when looping by 
+    /// `Swarm.update_buffered()`. Objects killed during the loop can still be read.
+    /// Returns None for spawns that were not spawned when the loop started or were 
+    /// spawned again during the loop, and always when looping by `Swarm.update()`.
+    pub fn previous(&self, spawn: &Spawn) -> Option<&ItemType> {
+        let tag = spawn.0.borrow();
+        match self.started.get(tag.id) {
+            Some(Some((pos, gen))) if tag.gen == *gen || (!tag.active && tag.gen == gen.wrapping_add(1)) => 
+                self.previous.get(*pos),
+            _ => None,
+        }
+    }
+
+    /// Returns the Spawn of a WeakSpawn if its object is still alive, see WeakSpawn.upgrade()
+    pub fn upgrade(&self, weak: &WeakSpawn) -> Option<Spawn> {
+        weak.upgrade_in(&self.spawns[..self.len])
+    }
+
     /// Returns the number of spawned instances currently availeble
     pub fn count(&self) -> usize { self.len }
 
     /// Returns the maximum number of instances that can be spawned
+    #[allow(clippy::clone_on_copy)]
     pub fn capacity(&self) -> usize { self.max.clone() }
 
     /// Loop through spawned instances until the `predicate` callback returns true.
@@ -146,13 +179,14 @@ where ItemType: Default + Clone {
     ///         }
     ///     });
     ///```
+    #[allow(clippy::op_ref, clippy::needless_return)]
     pub fn find<Predicate> (&self, predicate: Predicate) -> Option<Spawn> 
     where Predicate: Fn(&ItemType) -> bool {
         let count = self.len;
         let mut i = 0;
 
         while &i < &count {
-            if predicate(&self.pool[self.order[i]]) { 
+            if predicate(&self.pool[i]) { 
                 return Some(self.spawns[i].mirror());
             }
             i += 1;
@@ -167,13 +201,14 @@ where ItemType: Default + Clone {
     /// None will be returned.
     /// 
     /// This methode functions in the opposite way as the find methode.
+    #[allow(clippy::op_ref, clippy::needless_return)]
     pub fn for_while<Predicate> (&self, predicate: Predicate) -> Option<Spawn> 
     where Predicate: Fn(&ItemType) -> bool {
         let count = self.len;
         let mut i = 0;
 
         while &i < &count {
-            if predicate(&self.pool[self.order[i]]) { 
+            if !predicate(&self.pool[i]) { 
                 return Some( self.spawns[i].mirror());
             }
             i += 1;
@@ -186,7 +221,9 @@ where ItemType: Default + Clone {
     /// instances so that the object data of each looped instance can be changed.
     ///
     /// This methode functions the same as the Swarm.enumerate() methode.
+    #[allow(clippy::op_ref)]
     pub fn enumerate(&mut self, handler: EnumerateHandler<ItemType>) {
+        self.mark_active();
         let len = self.len;
         let mut i = 0;
 
@@ -200,12 +237,14 @@ where ItemType: Default + Clone {
     /// Loop through all spawned instances and edit them.
     /// 
     /// This methode functions the same as the Swarm.for_each() methode.
+    #[allow(clippy::op_ref)]
     pub fn for_each(&mut self, handler: ForEachHandler<ItemType>) {
+        self.mark_active();
         let count = self.len;
         let mut i = 0;
 
         while &i < &count {
-            handler(&mut self.pool[self.order[i]]);
+            handler(&mut self.pool[i]);
             i += 1;
         }
     }
@@ -214,12 +253,14 @@ where ItemType: Default + Clone {
     /// callback handler
     /// 
     /// This methode functions the same as the Swarm.for_all() methode.
+    #[allow(clippy::op_ref, clippy::needless_borrow)]
     pub fn for_all(&mut self, handler: ForAllHandler<ItemType, Properties>) {
+        self.mark_active();
         let len = self.len;
         let mut i = 0;
 
         while &i < &len {
-            handler(&i, &mut self.pool, &mut self.properties);
+            handler(&i, &mut self.pool[..len], &mut self.properties);
             i += 1;
         }
     }
@@ -249,15 +290,72 @@ where ItemType: Default + Clone {
     ///     assert_eq!(swarm.count(), 2);
     ///```
     pub fn spawn(&mut self) -> Option<Spawn> {
+        let spawn = self.activate()?;
+
+        // slots are constructed the first time they are spawned
+        if self.pool.len() < self.len {
+            self.pool.push((self.constructor)());
+        }
+        self.record(Op::Spawn { id: spawn.id() });
+        Some(spawn)
+    }
+
+    /// Create a new pool instance with the given `value`, see SwarmControl.spawn().
+    /// The value replaces the object that was stored in a recycled slot.
+    /// 
+    /// Returns None, and drops the value, if the pool reached it's maximum capacity.
+    pub fn spawn_with(&mut self, value: ItemType) -> Option<Spawn> {
+        let spawn = self.activate()?;
+
+        if self.pool.len() < self.len {
+            self.pool.push(value);
+        } else {
+            self.pool[self.len - 1] = value;
+        }
+        self.record(Op::SpawnWith { id: spawn.id() });
+        if let (Some(log), Some(encode)) = (self.recorder.as_mut(), self.encoder) {
+            let mut bytes = Vec::new();
+            encode(&self.pool[self.len - 1], &mut bytes);
+            log.push_value(bytes);
+        }
+        Some(spawn)
+    }
+
+    // stamps the object of a spawn as changed, if the swarm tracks changes
+    fn mark(&self, spawn: &Spawn) {
+        if self.tracking { spawn.0.borrow_mut().changed = Some(self.ticks); }
+    }
+
+    fn mark_active(&self) {
+        if !self.tracking { return; }
+        for spawn in &self.spawns[..self.len] { self.mark(spawn); }
+    }
+
+    // adds an operation to the log of a recording swarm, see Swarm.start_recording()
+    fn record(&mut self, op: Op) {
+        if let Some(log) = self.recorder.as_mut() { log.push(op); }
+    }
+
+    // takes the next free spawn and activates it at the end of the pool
+    #[allow(clippy::len_zero, clippy::manual_inspect)]
+    fn activate(&mut self) -> Option<Spawn> {
         if self.len < *self.max {
 
             self.len += 1;
             let pos = self.len - 1;
+            let born = self.ticks;
+            let changed = if self.tracking { Some(born) } else { None };
+
+            self.counters.spawns += 1;
+            self.counters.peak_len = self.counters.peak_len.max(self.len);
      
             if self.free.len() > 0 {
                 self.free.pop().map(|s| { 
                     s.0.borrow_mut().pos = pos; 
                     s.0.borrow_mut().active = true;
+                    s.0.borrow_mut().ttl = None;
+                    s.0.borrow_mut().born = born;
+                    s.0.borrow_mut().changed = changed;
                     s 
                 })
             } else {
@@ -265,20 +363,82 @@ where ItemType: Default + Clone {
                 let s = &self.spawns[pos];
                     s.0.borrow_mut().pos = pos;
                     s.0.borrow_mut().active = true;
+                    s.0.borrow_mut().ttl = None;
+                    s.0.borrow_mut().born = born;
+                    s.0.borrow_mut().changed = changed;
     
                 Some(s.mirror())
             }
         } else {
+            self.counters.failed_spawns += 1;
             None
         }
     }
 
+    /// Set the number of ticks a spawn has left to live, see Swarm.tick().
+    /// Passing None lets the spawn live forever.
+    pub fn set_ttl(&mut self, spawn: &Spawn, ticks: Option<usize>) {
+        if spawn.active() { 
+            spawn.0.borrow_mut().ttl = ticks; 
+            self.record(Op::SetTtl { id: spawn.id(), ttl: ticks });
+        }
+    }
+
+    /// Add a spawn to a group, the group is created if it does not exist yet.
+    /// Inactive spawns and spawns that are already a member of the group are ignored.
+    /// 
+    /// # Example
+    /// ```
+    ///     extern crate swarm_pool;
+    ///     use swarm_pool::{ Swarm, Spawn };
+    /// 
+    ///     const PROJECTILES: usize = 0;
+    /// 
+    ///     let mut swarm = Swarm::<u8, _>::new(10, ());
+    ///     let _shooter = &swarm.spawn().unwrap();
+    ///     
+    ///     swarm.update(|ctl| {
+    ///         if let Some(bullet) = ctl.spawn() {
+    ///             ctl.add_to_group(&bullet, PROJECTILES);
+    ///         }
+    ///     });
+    ///     assert_eq!(swarm.iter_group(PROJECTILES).count(), 1);
+    ///```
+    pub fn add_to_group(&mut self, spawn: &Spawn, group_id: GroupId) {
+        if !spawn.active() { return; }
+
+        match self.groups.iter_mut().find(|g| g.group_id == group_id) {
+            Some(group) => {
+                if group.members.contains(spawn) { return; }
+                group.members.push(spawn.mirror()); 
+            },
+            None => self.groups.push(Group { group_id, members: vec![spawn.mirror()] }),
+        }
+        spawn.0.borrow_mut().groups += 1;
+    }
+
+    /// Remove a spawn from a group, nothing happens if the spawn was not a member.
+    pub fn remove_from_group(&mut self, spawn: &Spawn, group_id: GroupId) {
+        if let Some(group) = self.groups.iter_mut().find(|g| g.group_id == group_id) {
+            let members = group.members.len();
+            group.members.retain(|s| s != spawn);
+            if group.members.len() < members { spawn.0.borrow_mut().groups -= 1; }
+        }
+    }
+
+    /// Returns true if the spawn is a member of the group
+    pub fn in_group(&self, spawn: &Spawn, group_id: GroupId) -> bool {
+        self.groups.iter()
+            .any(|g| g.group_id == group_id && g.members.contains(spawn))
+    }
+
     /// Remove the currently updating spawn instance, see SwarmControl.kill()
     pub fn kill_current(&mut self) {
         self.kill(&self.target_spawn())
     }
 
-    /// Remove a spawn instance from the swarm pool update loops
+    /// Remove a spawn instance from the swarm pool update loops.
+    /// Spawns that are no longer active are ignored.
     /// 
     /// **NOTE**: Spawns killed by SwarmControl will be excluded the next time 
     /// Swarm.update() is called.
@@ -299,36 +459,50 @@ where ItemType: Default + Clone {
     ///     assert_eq!(swarm.count(), 0);
     ///```
     pub fn kill(&mut self, target: &Spawn) {
-        target.0.borrow_mut().active = false;
+        // killing twice would recycle the slot twice
+        if !target.active() { return; }
+        self.record(Op::Kill { id: target.id() });
+
+        {
+            let mut tag = target.0.borrow_mut();
+            tag.active = false;
+            tag.pending = false;
+            tag.gen = tag.gen.wrapping_add(1);   // outdates scoped guards of this spawn
+            tag.died = Some(self.ticks);
+        }
     
         let last_pos = self.len - 1;
         let target_pos = target.pos();
     
         if self.len > 1 && target_pos < last_pos {
             
-            let last_val = self.pool[last_pos].clone();
-            let target_val = self.pool[target_pos].clone();
-    
             // swap content to back
-            self.pool[target_pos] = last_val;
-            self.pool[last_pos] = target_val;
+            self.pool.swap(target_pos, last_pos);
     
             // swap spawns equally
-            self.spawns[target_pos] = self.spawns[last_pos].mirror();
-            self.spawns[last_pos] = target.mirror();
+            self.spawns.swap(target_pos, last_pos);
     
             // set swapped spawn pool pointer to point to their new location
             self.spawns[target_pos].0.borrow_mut().pos = target_pos;
             self.spawns[last_pos].0.borrow_mut().pos = last_pos;
-    
-            if target_pos > self.pos { self.order[target_pos] = last_pos; }
-            if last_pos > self.pos { self.order[last_pos] = target_pos; }
         }
     
+ 
+        // killed spawns are no longer part of any group, spawns without groups skip the search
+        if target.0.borrow().groups > 0 {
+            for group in self.groups.iter_mut() {
+                group.members.retain(|s| s != target);
+            }
+            target.0.borrow_mut().groups = 0;
+        }
+
         // store and decrement size             
         if self.len > 0 { 
             self.free.push(target.mirror());
             self.len -= 1; 
+
+            self.counters.kills += 1;
+            self.counters.lifetimes += self.ticks - target.0.borrow().born;
         }
     }
 }
\ No newline at end of file
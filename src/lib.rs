@@ -4,11 +4,14 @@
 //! and provides update loops to iterate over them.
 //! 
 //! In order to create a new swarm pool, you need to define what your `pool object` and `swarm properties` types
-//! are going to look like. Your `pool object` must at leas implement the Default and Clone traits 
-//! from the standard library. The `swarm properties`, on the other hand, does not depend on any traits.
+//! are going to look like. Your `pool object` does not have to implement any traits,
+//! `Swarm::new()` constructs pool objects using Default, `Swarm::new_with()` takes a constructor instead.
+//! Pool objects are only constructed when their slot is spawned for the first time. The `swarm properties`
+//! does not depend on any traits either.
 //! 
 //! # Basic swarm setup example
-//! ```
+#![cfg_attr(feature = "alloc", doc = "```")]
+#![cfg_attr(not(feature = "alloc"), doc = "```ignore")]
 //! extern crate swarm_pool;
 //! use swarm_pool::Swarm;
 //! 
@@ -28,14 +31,16 @@
 //! }
 //! ```
 //! 
-//! The swarm is now ready to be used. First of all we need to Spawn new pool instances. In reality all
-//! objects in the pool are allready created and are waiting to be used. This means that all objects (
-//! from 0 up to, but not including, the maximum capacity) can be accessed through the fetch() methode.
-//! The difference between spawned and non-spawned pool objects is that spawned object are included in all
-//! of the Swarm pools iterator methodes and non-spawned object are not.
+//! The swarm is now ready to be used. First of all we need to Spawn new pool instances. A new swarm does
+//! not create any objects up front, a slot is constructed the first time it is spawned, by `Default` or
+//! by the constructor passed to `Swarm::new_with()`. Killed objects stay in the pool and are reused by
+//! the next spawn, so once every slot was used, spawning no longer constructs objects.
+//! Objects are fetched through the Spawn that `spawn()` returns, and only spawned objects are included
+//! in the Swarm pools iterator methodes.
 //!
 //! # Spawning and looping
-//! ```
+#![cfg_attr(feature = "alloc", doc = "```")]
+#![cfg_attr(not(feature = "alloc"), doc = "```ignore")]
 //! # extern crate swarm_pool;
 //! # use swarm_pool::Swarm;
 
@@ -66,7 +71,8 @@
 //! pool objects, `update` is easy to use, gives full control, but is slow (less than half the speed).
 //!
 //! # Cross referencing using for_all & update
-//! ```
+#![cfg_attr(feature = "alloc", doc = "```")]
+#![cfg_attr(not(feature = "alloc"), doc = "```ignore")]
 //! # extern crate swarm_pool;
 //! # use swarm_pool::{ Swarm, Spawn };
 
@@ -135,31 +141,80 @@
 //! There are many more functionalities included in the Swarm and SwarmControl types. 
 //! The documentation on the examples above or other functionalities this library provides are more in depth
 //! and should be read, for writing them out was a lot of work ;)
+//!
+//! # Features
+//! The `std` feature is enabled by default. Without it the crate is `#![no_std]`:
+//! - with the `alloc` feature, `Swarm` and `SwarmControl` are available on targets that have
+//!   an allocator but no standard library, such as WASM without std. The `world` and `tools` 
+//!   modules require `std`.
+//! - without any features, only the heap free `static_swarm::StaticSwarm` and the callback
+//!   types are available.
+//! 
+//! The `integrity-checks` feature makes debug builds call `Swarm::check_integrity()` after 
+//! every mutation, and panic when the swarm is corrupted.
+
 
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(all(test, feature = "std"))]
 #[allow(dead_code)]
 mod tests;
+#[cfg(feature = "alloc")]
 pub mod control;
+#[cfg(feature = "alloc")]
+pub mod record;
+#[cfg(feature = "alloc")]
+pub mod replication;
 pub mod types;
-//pub mod tools;
+pub mod static_swarm;
+#[cfg(feature = "std")]
+pub mod world;
+#[cfg(feature = "std")]
+pub mod tools;
 
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::{ vec, vec::Vec };
+#[cfg(feature = "alloc")]
+use alloc::rc::Rc;
+#[cfg(feature = "alloc")]
+use core::{ cell::RefCell, fmt };
+#[cfg(feature = "alloc")]
 use control::SwarmControl;
+#[cfg(feature = "alloc")]
+use record::{ Op, OpLog, ReplayError };
 pub use types::*;
 
 /// The actual Swarm pool
+#[cfg(feature = "alloc")]
 pub struct Swarm<ItemType, Properties> {
     pool: Vec<ItemType>,
+    back: Vec<ItemType>,    // the second buffer of the double buffered loops
     spawns: Vec<Spawn>,
     free: Vec<Spawn>,
     len: usize,
     max: usize,
-    order: Vec<usize>,
+    order: Vec<SpawnId>,    // the spawn ids update() visits, in pool order
+    by_id: Vec<Spawn>,      // per spawn id, its spawn
+    started: Vec<Option<(ObjectPosition, u32)>>,    // per spawn id, its position and generation when update_buffered() started
     factories: Vec<Factory<ItemType, Properties>>,
+    groups: Vec<Group>,
+    expire: Option<ExpireHandler<ItemType, Properties>>,
+    constructor: ConstructHandler<ItemType>,
+    counters: Counters,
+    ticks: usize,
+    dropped: KillQueue,     // the spawns of dropped SpawnGuards
+    recorder: Option<OpLog>,
+    encoder: Option<EncodeHandler<ItemType>>,   // encodes the recorded values, see start_recording_with()
+    tracking: bool,         // stamp mutably accessed objects, see track_changes()
 
     pub properties: Properties,
 }
 
-impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
+#[cfg(feature = "alloc")]
+impl<ItemType: Default, Properties> Swarm<ItemType, Properties> {
 
     /// Create a new Swarm object pool
     /// 
@@ -175,11 +230,10 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// use swarm_pool::Swarm;
     /// 
     /// // create an object you want to pool
-    /// // Swarm uses Copy and therfore only accepts Sized properties!
-    /// // This means types such as String and Vec aren't allowed
-    /// // The tools module has a few tools that deal with this
-    
-    /// #[derive(Default, Clone)]     
+    /// // pool objects are constructed with Default, the first time their slot is spawned,
+    /// // use Swarm::new_with() for types that do not implement Default
+    ///
+    /// #[derive(Default)]     
     /// pub struct MyPoolObject {           
     ///     pub name: &'static str,              
     ///     pub value: usize,               
@@ -192,60 +246,99 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// assert!(swarm.capacity() == 10);
     /// ```
     pub fn new(capacity: usize, properties: Properties) -> Self {
+        Swarm::new_with(capacity, properties, ItemType::default)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<ItemType, Properties> Swarm<ItemType, Properties> {
+
+    /// Create a new Swarm object pool whose objects are created by a `constructor`,
+    /// see Swarm::new(). This makes it possible to pool types that do not implement
+    /// Default, or types that own resources which should not be created up front.
+    /// 
+    /// No pool objects are created until they are spawned for the first time, killed
+    /// objects are recycled by later spawns. All pool objects are dropped when the 
+    /// swarm is dropped.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// // has no sensible default and is not Clone
+    /// pub struct Emitter { particles: Vec<(f32, f32)> }
+    /// 
+    /// let mut swarm = Swarm::<Emitter, _>::new_with(1000, (), || Emitter { 
+    ///     particles: Vec::with_capacity(64),
+    /// });
+    /// 
+    /// let emitter = swarm.spawn().unwrap();
+    /// assert_eq!(swarm.fetch_ref(&emitter).particles.capacity(), 64);
+    /// ```
+    pub fn new_with(capacity: usize, properties: Properties, constructor: ConstructHandler<ItemType>) -> Self {
         let mut spawns = Vec::<Spawn>::with_capacity(capacity);
-        let mut order = Vec::<usize>::with_capacity(capacity);
+        let mut order = Vec::<SpawnId>::with_capacity(capacity);
 
         for i in 0..capacity { 
             let tag = Spawn::new(i);
             spawns.push(tag);
             order.push(i);
         }
+        let by_id = spawns.iter().map(Spawn::mirror).collect();
 
         Swarm { 
-            pool: vec![ItemType::default(); capacity],
+            pool: Vec::with_capacity(capacity),
+            back: Vec::new(),
             spawns,
             free: Vec::<Spawn>::with_capacity(capacity),
             len: 0,
             max: capacity,
             order,
+            by_id,
+            started: Vec::new(),
             properties,
             factories: Vec::new(),
+            groups: Vec::new(),
+            expire: None,
+            constructor,
+            counters: Counters::default(),
+            ticks: 0,
+            dropped: KillQueue::default(),
+            recorder: None,
+            encoder: None,
+            tracking: false,
         }
     }
-    /// Create a new spawn for every item in the `items` list and gives
-    /// it that value.
-    /// 
-    /// # Example
-    /// ```
-    ///     extern crate swarm_pool;
-    ///     use swarm_pool::{ Swarm, Spawn };
-    /// 
-    ///     let mut swarm = Swarm::<u8, _>::new(10, ());
-    ///     swarm.populate(&[5, 4, 3, 2, 1]);
-    ///     
-    ///     assert_eq!(swarm.count(), 5);
-    ///     assert_eq!(*swarm.fetch_raw(&0), 5);
-    /// ```
-    pub fn populate(&mut self, items: &[ItemType]) {
-        for item in items {
-            if let Some(s) = self.spawn() {
-                *self.fetch(&s) = item.clone();
-            }
-        }
+
+    pub(crate) fn control(&mut self) -> SwarmControl<'_, ItemType, Properties> {
+        self.split_control().0
     }
-    
-    pub(crate) fn control(&mut self) -> SwarmControl<ItemType, Properties> {
-        SwarmControl {
+
+    // hands out a control, next to the spawn lookup table and the length of the swarm, 
+    // so the update loop can read the table and write back the length while the control is alive
+    fn split_control(&mut self) -> (SwarmControl<'_, ItemType, Properties>, &[Spawn], &mut usize) {
+        let ctl = SwarmControl {
             order: &mut self.order,
             pos: 0,
+            previous: &[],
+            started: &[],
             len: self.len,
             max: &self.max, 
             spawns: &mut self.spawns, 
             free: &mut self.free,
+            groups: &mut self.groups,
+            constructor: self.constructor,
+            counters: &mut self.counters,
+            recorder: &mut self.recorder,
+            encoder: self.encoder,
+            ticks: self.ticks,
+            tracking: self.tracking,
 
             pool: &mut self.pool, 
             properties: &mut self.properties,
-        }
+        };
+        (ctl, &self.by_id, &mut self.len)
     }
 
     /// Add methodes as factories to the swarm that can be used to create
@@ -294,7 +387,6 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     ///     assert_eq!(swarm.fetch_ref(&truck).value, 2);
     /// }
     /// ``` 
-
     pub fn add_factory(&mut self, type_def: usize, factory_handler: FactoryHandler<ItemType, Properties>) {
         self.factories.push(Factory { type_def, methode: factory_handler })
     }
@@ -330,15 +422,222 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// }
     /// ```
     pub fn spawn(&mut self) -> Option<Spawn> {
+        self.kill_dropped();
         let mut ctl = self.control();
         let result = ctl.spawn();
         self.len = ctl.len;
+        self.debug_check();
         result
     }
 
+    /// Create a new pool instance with the given `value`. A slot that is spawned for the
+    /// first time is constructed from the value, a recycled slot drops its old object.
+    /// 
+    /// Returns None, and drops the value, if the pool reached it's maximum capacity.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<String, _>::new(10, ());
+    /// let greeting = swarm.spawn_with("hello".to_string()).unwrap();
+    /// 
+    /// assert_eq!(swarm.fetch_ref(&greeting), "hello");
+    /// ```
+    pub fn spawn_with(&mut self, value: ItemType) -> Option<Spawn> {
+        self.kill_dropped();
+        let mut ctl = self.control();
+        let result = ctl.spawn_with(value);
+        self.len = ctl.len;
+        self.debug_check();
+        result
+    }
+
+    /// Create a new pool instance that is killed automatically after it lived for 
+    /// a number of `ticks`, see `tick()`. 
+    /// 
+    /// Returns None if the pool reached it's maximum capacity.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u8, _>::new(10, ());
+    /// let bullet = swarm.spawn_with_ttl(2).unwrap();
+    /// 
+    /// swarm.tick();
+    /// assert!(bullet.active());
+    /// assert_eq!(bullet.ttl(), Some(1));
+    /// 
+    /// swarm.tick();
+    /// assert!(!bullet.active());
+    /// assert_eq!(swarm.count(), 0);
+    /// ```
+    pub fn spawn_with_ttl(&mut self, ticks: usize) -> Option<Spawn> {
+        let spawn = self.spawn();
+        if let Some(s) = &spawn {
+            self.set_ttl(s, Some(ticks));
+        }
+        spawn
+    }
+
+    /// Set the number of ticks a spawn has left to live, see `tick()`.
+    /// Passing None lets the spawn live forever. Inactive spawns are ignored.
+    pub fn set_ttl(&mut self, spawn: &Spawn, ticks: Option<usize>) {
+        self.control().set_ttl(spawn, ticks);
+    }
+
+    /// Set a callback handler that is called for every spawn whose lifetime expired 
+    /// during `tick()`. The handler receives the pool object before it is killed and recycled.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// // count the number of expired particles
+    /// let mut swarm = Swarm::<u8, usize>::new(10, 0);
+    /// swarm.on_expire(|_particle, expired| *expired += 1);
+    /// 
+    /// swarm.spawn_with_ttl(1);
+    /// swarm.spawn_with_ttl(1);
+    /// swarm.spawn_with_ttl(5);
+    /// swarm.tick();
+    /// 
+    /// assert_eq!(swarm.properties, 2);
+    /// assert_eq!(swarm.count(), 1);
+    /// ```
+    pub fn on_expire(&mut self, expire_handler: ExpireHandler<ItemType, Properties>) {
+        self.expire = Some(expire_handler);
+    }
+
+    /// Advance the swarm by one tick. The lifetime of every spawn that has one is 
+    /// decreased by one, spawns whose lifetime runs out are killed. Expired spawns 
+    /// are removed in a single pass over the pool.
+    pub fn tick(&mut self) {
+        self.kill_dropped();
+        self.record(Op::Tick);
+        self.unrecorded(Self::expire_tick);
+        self.debug_check();
+    }
+
+    // ages all spawns by one tick and kills the expired ones
+    fn expire_tick(&mut self) {
+        self.ticks += 1;
+        let mut pos = 0;
+
+        while pos < self.len {
+            let expired = {
+                let mut tag = self.spawns[pos].0.borrow_mut();
+                match tag.ttl {
+                    Some(ttl) if ttl <= 1 => true,
+                    Some(ttl) => { tag.ttl = Some(ttl - 1); false },
+                    None => false,
+                }
+            };
+
+            if expired {
+                if let Some(expire) = self.expire {
+                    expire(&mut self.pool[pos], &mut self.properties);
+                }
+                // killing moves the last spawn into this position, so it is visited next
+                let spawn = self.spawns[pos].mirror();
+                let mut ctl = self.control();
+                ctl.kill(&spawn);
+                self.len = ctl.len;
+            } else {
+                pos += 1;
+            }
+        }
+    }
+
+    /// Returns the number of times tick() has been called
+    pub fn ticks(&self) -> usize { self.ticks }
+
+    /// Enable or disable change tracking. A tracking swarm stamps a pool object with the 
+    /// current tick whenever it is accessed mutably, so a renderer or network layer only 
+    /// has to process the objects that changed, see `iter_changed_since()`.
+    /// Change tracking is disabled by default.
+    /// 
+    /// Objects are stamped when they are spawned, fetched by `fetch()`, `fetch_raw()`, 
+    /// `SwarmControl.target()` or `SwarmControl.fetch()`, and by every loop that hands 
+    /// them out mutably. Changes made directly through `SwarmControl.pool` are not tracked.
+    /// The stamps are part of the spawn state, `restore_state()` rolls them back as well.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u32, _>::new(10, ());
+    /// swarm.track_changes(true);
+    /// let tree = swarm.spawn_with(1).unwrap();
+    /// let rock = swarm.spawn_with(2).unwrap();
+    /// swarm.tick();
+    /// 
+    /// // upload what changed during this frame
+    /// let frame = swarm.ticks();
+    /// *swarm.fetch(&rock) += 1;
+    /// swarm.kill(&tree);
+    /// let bush = swarm.spawn_with(5).unwrap();
+    /// 
+    /// let changed: Vec<_> = swarm.iter_changed_since(frame).map(|(s, v)| (s.id(), *v)).collect();
+    /// assert_eq!(changed, vec![(rock.id(), 3), (bush.id(), 5)]);
+    /// assert_eq!(swarm.iter_added_since(frame).collect::<Vec<_>>(), vec![bush.mirror()]);
+    /// assert_eq!(swarm.iter_removed_since(frame).collect::<Vec<_>>(), vec![tree.id()]);
+    /// 
+    /// swarm.clear_changed();
+    /// assert_eq!(swarm.iter_changed_since(0).count(), 0);
+    /// ```
+    pub fn track_changes(&mut self, enabled: bool) {
+        self.tracking = enabled;
+    }
+
+    /// Returns true if change tracking is enabled, see `track_changes()`
+    pub fn tracks_changes(&self) -> bool { self.tracking }
+
+    /// Returns the spawned objects, and their spawns, that were changed at or after 
+    /// the given `tick`, see `track_changes()`.
+    pub fn iter_changed_since(&self, tick: usize) -> impl Iterator<Item = (Spawn, &ItemType)> {
+        self.spawns[..self.len].iter().zip(&self.pool)
+            .filter(move |(s, _)| s.0.borrow().changed.is_some_and(|t| t >= tick))
+            .map(|(s, item)| (s.mirror(), item))
+    }
+
+    /// Forget all changes, objects are returned by `iter_changed_since()` again once 
+    /// they change after this call.
+    pub fn clear_changed(&mut self) {
+        for spawn in &self.by_id {
+            spawn.0.borrow_mut().changed = None;
+        }
+    }
+
+    /// Returns the active spawns that were spawned at or after the given `tick`.
+    /// Spawning is tracked whether change tracking is enabled or not.
+    pub fn iter_added_since(&self, tick: usize) -> impl Iterator<Item = Spawn> + '_ {
+        self.spawns[..self.len].iter()
+            .filter(move |s| s.0.borrow().born >= tick)
+            .map(Spawn::mirror)
+    }
+
+    /// Returns the ids of the spawns that were killed at or after the given `tick`, 
+    /// including spawns that expired. A spawn that was killed and spawned again is 
+    /// returned by both this methode and `iter_added_since()`.
+    /// Kills are tracked whether change tracking is enabled or not.
+    pub fn iter_removed_since(&self, tick: usize) -> impl Iterator<Item = SpawnId> + '_ {
+        self.by_id.iter()
+            .filter(move |s| s.0.borrow().died.is_some_and(|t| t >= tick))
+            .map(Spawn::id)
+    }
+
     /// Create a new pool instance with specific values. The instances values are
     /// set by passing it through a predefined factory. See `add_factory(type_def, methode)`
     /// 
+    /// Returns None, without spawning, if no factory was added for the `type_def` or if 
+    /// the pool reached it's maximum capacity.
+    /// 
     /// # Example
     /// ```
     /// extern crate swarm_pool;
@@ -380,25 +679,18 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     ///     assert_eq!(swarm.fetch_ref(&truck_2).name, "truck");
     /// }
     /// ```
-    
     pub fn spawn_type(&mut self, type_def: usize) -> Option<Spawn> {
-        let some_spawn = self.spawn().clone();
+        // unknown types are not spawned at all
+        let methode = self.factories.iter().find(|x| x.type_def == type_def)?.methode;
+        let spawn = self.unrecorded(Self::spawn)?;
 
-        if let (Some(factory), Some(spawn)) = (
-            self.factories
-                .iter_mut()
-                .find(|x| x.type_def == type_def),
-            some_spawn
-        ) {
-            (factory.methode)(&mut self.pool[spawn.0.borrow().pos], &mut self.properties);
-            Some(spawn.mirror())
-        }
-        else {
-            None
-        }
+        methode(&mut self.pool[spawn.pos()], &mut self.properties);
+        self.record(Op::SpawnType { type_def, id: spawn.id() });
+        Some(spawn)
     }
 
-    /// Remove a spawn instance from the swarm pool update loops
+    /// Remove a spawn instance from the swarm pool update loops.
+    /// Spawns that are no longer active are ignored.
     /// 
     /// # Example
     /// ```
@@ -427,19 +719,312 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// }
     /// ```
     pub fn kill(&mut self, target: &Spawn) {
+        self.kill_dropped();
         let mut ctl = self.control();
-        let reset_order = target.pos();
         ctl.kill(target);
         self.len = ctl.len;
-        self.order[reset_order] = reset_order;
+        self.debug_check();
+    }
+
+    /// Create a new pool instance that is killed when the returned guard is dropped,
+    /// unless the guard is turned into a plain Spawn by `into_inner()` or `forget()`.
+    /// Useful for temporaries such as a preview ghost or a selection box.
+    /// 
+    /// The guard does not borrow the swarm. The kill is queued when the guard is dropped
+    /// and executed the next time the swarm spawns, kills, ticks or updates, 
+    /// see `kill_dropped()`.
+    /// 
+    /// Returns None if the pool reached it's maximum capacity.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u8, _>::new(10, ());
+    /// {
+    ///     let ghost = swarm.spawn_scoped().unwrap();
+    ///     *swarm.fetch(&ghost) = 42;
+    ///     assert_eq!(swarm.count(), 1);
+    /// }
+    /// swarm.kill_dropped();
+    /// assert_eq!(swarm.count(), 0);
+    /// 
+    /// // keep it after all
+    /// let tower = swarm.spawn_scoped().unwrap().into_inner();
+    /// swarm.kill_dropped();
+    /// assert!(tower.active());
+    /// ```
+    pub fn spawn_scoped(&mut self) -> Option<SpawnGuard> {
+        let spawn = self.spawn()?;
+        Some(SpawnGuard::new(spawn, Rc::clone(&self.dropped)))
+    }
+
+    /// Kill the objects of all SpawnGuards that were dropped since the last call.
+    /// This happens automatically when the swarm spawns, kills, ticks or updates. 
+    /// Objects that were killed before their guard was dropped are left alone, 
+    /// even if their slot was spawned again.
+    pub fn kill_dropped(&mut self) {
+        if self.dropped.borrow().is_empty() { return; }
+
+        let mut dropped = core::mem::take(&mut *self.dropped.borrow_mut());
+        let mut ctl = self.control();
+        for (spawn, gen) in dropped.drain(..) {
+            if spawn.active() && spawn.gen() == gen {
+                ctl.kill(&spawn);
+            }
+        }
+        self.len = ctl.len;
+
+        // hand back the buffer, keeping its capacity
+        let mut queue = self.dropped.borrow_mut();
+        if queue.is_empty() { *queue = dropped; }
+        drop(queue);
+        self.debug_check();
+    }
+
+    /// Start logging every spawn, kill, kill_all, populate, set_ttl and tick, including the ones 
+    /// made through SwarmControl, see the `record` module. A recording that is already 
+    /// running is restarted.
+    /// 
+    /// The values passed to `spawn_with()` and `populate()` are not logged, use 
+    /// `start_recording_with()` to log them as well.
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(OpLog::new());
+        self.encoder = None;
+    }
+
+    /// Start recording like `start_recording()` does, and log the values passed to 
+    /// `spawn_with()` and `populate()` as well, encoded by the `encoder` callback handler.
+    /// Use `replay_with()` to replay the log with its values.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u16, ()>::new(10, ());
+    /// let start = swarm.save_state();
+    /// swarm.start_recording_with(|value, bytes| bytes.extend_from_slice(&value.to_le_bytes()));
+    /// 
+    /// swarm.spawn_with(300);
+    /// swarm.populate(&[7, 8]);
+    /// let log = swarm.stop_recording().unwrap();
+    /// 
+    /// let mut replayed = Swarm::<u16, ()>::new(10, ());
+    /// replayed.restore_state(&start);
+    /// replayed.replay_with(&log, |bytes| Some(u16::from_le_bytes([*bytes.get(0)?, *bytes.get(1)?]))).unwrap();
+    /// assert_eq!(replayed.active_slice(), &[300, 7, 8]);
+    /// ```
+    pub fn start_recording_with(&mut self, encoder: EncodeHandler<ItemType>) {
+        self.recorder = Some(OpLog::new());
+        self.encoder = Some(encoder);
+    }
+
+    /// Stop recording and return the log, or None if the swarm was not recording
+    pub fn stop_recording(&mut self) -> Option<OpLog> {
+        self.encoder = None;
+        self.recorder.take()
+    }
+
+    /// Returns the log of the running recording, or None if the swarm is not recording
+    pub fn recording(&self) -> Option<&OpLog> {
+        self.recorder.as_ref()
+    }
+
+    /// Replay a recorded log. The swarm should be in the state the recording started at,
+    /// for example by restoring a state saved when the recording started. Replayed 
+    /// operations are not recorded.
+    /// 
+    /// The values passed to `spawn_with()`, `populate()` and spawned by `apply_delta()` are 
+    /// not replayed, their slots are spawned like `spawn()` does, or constructed for spawns
+    /// of a delta. Use `replay_with()` to replay the recorded values.
+    /// 
+    /// Returns a ReplayError, and stops replaying, if a spawn gets another id than it 
+    /// was recorded with, or a recorded kill targets an inactive spawn.
+    pub fn replay(&mut self, log: &OpLog) -> Result<(), ReplayError> {
+        self.run_replay(log, None)
+    }
+
+    /// Replay a log recorded by `start_recording_with()` like `replay()` does, and spawn 
+    /// the recorded values decoded by the `decoder` callback handler. So replaying from 
+    /// the state the recording started at, rebuilds the same pool objects as well.
+    /// 
+    /// Returns a ReplayError as `replay()` does, and also if a value is missing from the 
+    /// log or cannot be decoded.
+    pub fn replay_with(&mut self, log: &OpLog, decoder: DecodeHandler<ItemType>) -> Result<(), ReplayError> {
+        self.run_replay(log, Some(decoder))
+    }
+
+    fn run_replay(&mut self, log: &OpLog, decoder: Option<DecodeHandler<ItemType>>) -> Result<(), ReplayError> {
+        // the next recorded value when replaying values, Some(None) if it is missing or invalid
+        let mut values = log.values().iter();
+        let mut next_value = || decoder.map(|decode| values.next().and_then(|bytes| decode(bytes)));
+
+        self.unrecorded(|swarm| {
+            for (index, op) in log.ops().iter().enumerate() {
+                let diverged = Err(ReplayError { index, op: *op });
+                let mut spawn_next = |swarm: &mut Self| match next_value() {
+                    Some(value) => value.and_then(|value| swarm.spawn_with(value)),
+                    None => swarm.spawn(),
+                };
+
+                match *op {
+                    Op::Spawn { id } => {
+                        if swarm.spawn().map(|s| s.id()) != Some(id) { return diverged; }
+                    },
+                    Op::SpawnWith { id } => {
+                        if spawn_next(swarm).map(|s| s.id()) != Some(id) { return diverged; }
+                    },
+                    Op::SpawnAs { id, gen } => {
+                        if swarm.by_id.get(id).filter(|s| !s.active()).is_none() { return diverged; }
+                        let value = match next_value() {
+                            Some(Some(value)) => value,
+                            Some(None) => return diverged,
+                            None => (swarm.constructor)(),
+                        };
+                        swarm.spawn_as(id, gen, value);
+                        swarm.recycle_in_order();
+                    },
+                    Op::SpawnType { type_def, id } => {
+                        if swarm.spawn_type(type_def).map(|s| s.id()) != Some(id) { return diverged; }
+                    },
+                    Op::Kill { id } => match swarm.by_id.get(id) {
+                        Some(spawn) if spawn.active() => {
+                            let spawn = spawn.mirror();
+                            swarm.kill(&spawn);
+                        },
+                        _ => return diverged,
+                    },
+                    Op::SetTtl { id, ttl } => match swarm.by_id.get(id) {
+                        Some(spawn) if spawn.active() => spawn.0.borrow_mut().ttl = ttl,
+                        _ => return diverged,
+                    },
+                    Op::KillAll => swarm.kill_all(),
+                    Op::Populate { count } => {
+                        for _ in 0..count {
+                            if spawn_next(swarm).is_none() { return diverged; }
+                        }
+                    },
+                    Op::Tick => swarm.tick(),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // adds an operation to the log of a recording swarm
+    fn record(&mut self, op: Op) {
+        if let Some(log) = self.recorder.as_mut() { log.push(op); }
+    }
+
+    // adds the encoded values to the log of a swarm that records values
+    fn record_values(&mut self, values: &[ItemType]) {
+        if let (Some(log), Some(encode)) = (self.recorder.as_mut(), self.encoder) {
+            for value in values {
+                let mut bytes = Vec::new();
+                encode(value, &mut bytes);
+                log.push_value(bytes);
+            }
+        }
+    }
+
+    // runs `f` without recording the operations it makes
+    fn unrecorded<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let recorder = self.recorder.take();
+        let result = f(self);
+        self.recorder = recorder;
+        result
+    }
+
+    // stamps the object of a spawn as changed, if change tracking is enabled
+    fn mark(&self, spawn: &Spawn) {
+        if self.tracking { spawn.0.borrow_mut().changed = Some(self.ticks); }
+    }
+
+    fn mark_active(&self) {
+        if !self.tracking { return; }
+        for spawn in &self.spawns[..self.len] { self.mark(spawn); }
     }
 
     /// Remove all spawn instances
     pub fn kill_all(&mut self) {
+        self.dropped.borrow_mut().clear();
+        self.record(Op::KillAll);
+        for spawn in &self.spawns[..self.len] {
+            self.counters.kills += 1;
+            self.counters.lifetimes += self.ticks - spawn.0.borrow().born;
+        }
+        for spawn in &self.spawns[..self.len] {
+            let mut tag = spawn.0.borrow_mut();
+            tag.gen = tag.gen.wrapping_add(1);
+            tag.died = Some(self.ticks);
+            tag.groups = 0;
+        }
         for spawn in &mut self.spawns {
             spawn.0.borrow_mut().active = false;
         }
+        for group in &mut self.groups {
+            group.members.clear();
+        }
+
+        // all used slots are free now, the first one is recycled first
+        let used = self.len + self.free.len();
+        self.free.clear();
+        self.free.extend(self.spawns[..used].iter().rev().map(Spawn::mirror));
         self.len = 0;
+        self.debug_check();
+    }
+
+    /// Add a spawn to a group, the group is created if it does not exist yet.
+    /// Groups make it possible to iterate over a subset of spawns, such as "all enemies", 
+    /// without having to loop through the entire pool. Killed spawns are automatically
+    /// removed from all of their groups.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// const ENEMIES: usize = 0;
+    /// 
+    /// let mut swarm = Swarm::<usize, _>::new(10, ());
+    /// let player = swarm.spawn().unwrap();
+    /// let orc = swarm.spawn().unwrap();
+    /// let goblin = swarm.spawn().unwrap();
+    /// 
+    /// swarm.add_to_group(&orc, ENEMIES);
+    /// swarm.add_to_group(&goblin, ENEMIES);
+    /// swarm.for_each_in_group(ENEMIES, |hp| *hp += 10);
+    /// 
+    /// assert_eq!(*swarm.fetch_ref(&player), 0);
+    /// assert_eq!(*swarm.fetch_ref(&orc), 10);
+    /// assert_eq!(*swarm.fetch_ref(&goblin), 10);
+    /// 
+    /// swarm.kill(&orc);
+    /// assert_eq!(swarm.iter_group(ENEMIES).count(), 1);
+    /// ```
+    pub fn add_to_group(&mut self, spawn: &Spawn, group_id: GroupId) {
+        self.control().add_to_group(spawn, group_id);
+        self.debug_check();
+    }
+
+    /// Remove a spawn from a group, nothing happens if the spawn was not a member.
+    pub fn remove_from_group(&mut self, spawn: &Spawn, group_id: GroupId) {
+        self.control().remove_from_group(spawn, group_id);
+    }
+
+    /// Returns true if the spawn is a member of the group
+    pub fn in_group(&self, spawn: &Spawn, group_id: GroupId) -> bool {
+        self.iter_group(group_id).any(|s| s == spawn)
+    }
+
+    /// Returns an iterator over the spawns that are a member of the group.
+    /// An unknown group results in an empty iterator.
+    pub fn iter_group(&self, group_id: GroupId) -> impl Iterator<Item = &Spawn> {
+        self.groups.iter()
+            .filter(move |g| g.group_id == group_id)
+            .flat_map(|g| g.members.iter())
     }
 
     /// Returns a spawn reference object from an object position within the pool
@@ -447,9 +1032,15 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
         self.spawns[*pos].mirror()
     }
 
+    /// Returns the spawn with the given `id` if it is active, see Spawn.id()
+    pub fn spawn_by_id(&self, id: SpawnId) -> Option<Spawn> {
+        self.by_id.get(id).filter(|s| s.active()).map(Spawn::mirror)
+    }
+
     /// Returns a mutable reference to an object from the Swarm pool.
     /// The supplied Spawn reference object points out which object to return.
     pub fn fetch(&mut self, spawn: &Spawn) -> &mut ItemType { 
+        self.mark(spawn);
         &mut self.pool[spawn.0.borrow().pos]
     }
 
@@ -466,6 +1057,7 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// Use fetch() instead of fetch_raw() if you are going to kill spawns 
     /// at any point in your code.
     pub fn fetch_raw(&mut self, pos: &ObjectPosition) -> &mut ItemType { 
+        self.mark(&self.spawns[*pos]);
         &mut self.pool[*pos]
     }
 
@@ -474,6 +1066,258 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
 
     /// Returns the maximum number of instances that can be spawned
     pub fn capacity(&self) -> usize { self.max }
+
+    /// Reduce the capacity of the swarm to `capacity` slots, and release the memory of 
+    /// killed pool objects. Killed slots are constructed again when they are spawned.
+    /// A capacity above the current one only releases the killed objects. Spawns of 
+    /// released slots are never active again, and fetching their object panics.
+    /// 
+    /// NOTE: spawned instances are not relocated, they keep their id and slot. So the swarm
+    /// cannot shrink below its highest spawned id, and a single spawned instance with a high 
+    /// id blocks shrinking even when every other slot is free. In that case nothing changes 
+    /// and a ShrinkError holding the smallest possible capacity is returned. 
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u8, _>::new(1000, ());
+    /// let spawns: Vec<_> = (0..1000).map(|_| swarm.spawn().unwrap()).collect();
+    /// for spawn in &spawns[3..999] { swarm.kill(spawn); }
+    /// 
+    /// // the spawn with id 999 is still alive and blocks shrinking
+    /// let error = swarm.shrink_to(10).unwrap_err();
+    /// assert_eq!(error.min_capacity, 1000);
+    /// 
+    /// swarm.kill(&spawns[999]);
+    /// assert!(swarm.shrink_to(2).is_err());
+    /// assert!(swarm.shrink_to(10).is_ok());
+    /// assert_eq!(swarm.capacity(), 10);
+    /// assert!(spawns[..3].iter().all(|s| s.active()));
+    /// ```
+    pub fn shrink_to(&mut self, capacity: usize) -> Result<(), ShrinkError> {
+        self.kill_dropped();
+
+        let min_capacity = self.min_capacity();
+        if capacity < min_capacity {
+            return Err(ShrinkError { capacity, min_capacity });
+        }
+        let capacity = capacity.min(self.max);
+        let len = self.len;
+
+        // spawned instances keep their position, unused slots that fit are moved behind them
+        self.spawns.retain(|s| s.id() < capacity);
+        for (pos, spawn) in self.spawns.iter().enumerate().skip(len) {
+            spawn.0.borrow_mut().pos = pos;
+        }
+        self.pool.truncate(len);
+        self.free.clear();
+        self.back.clear();
+        self.started.clear();
+        self.order.truncate(capacity);
+        self.max = capacity;
+
+        // released spawns are never active again, and point past every slot of the pool
+        for spawn in self.by_id.drain(capacity..) {
+            let mut tag = spawn.0.borrow_mut();
+            tag.active = false;
+            tag.pos = usize::MAX;
+        }
+
+        self.pool.shrink_to_fit();
+        self.spawns.shrink_to_fit();
+        self.free.shrink_to(capacity);
+        self.back.shrink_to_fit();
+        self.started.shrink_to_fit();
+        self.order.shrink_to_fit();
+        self.by_id.shrink_to_fit();
+
+        self.debug_check();
+        Ok(())
+    }
+
+    /// Reduce the capacity of the swarm as far as the spawned instances allow, 
+    /// see `shrink_to()`.
+    pub fn shrink_to_fit(&mut self) {
+        self.kill_dropped();
+        let min_capacity = self.min_capacity();
+        // cannot fail, the capacity is the minimum
+        let _ = self.shrink_to(min_capacity);
+    }
+
+    // the smallest capacity that holds the ids of all spawned instances
+    fn min_capacity(&self) -> usize {
+        self.spawns[..self.len].iter().map(|s| s.id() + 1).max().unwrap_or(0)
+    }
+
+    /// Returns the usage statistics of this swarm since it was created, or since
+    /// `reset_stats()` was called. Keeping the statistics only costs a few additions 
+    /// per spawn and kill, so they are always available.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u8, _>::new(2, ());
+    /// let a = swarm.spawn().unwrap();
+    /// swarm.spawn();
+    /// swarm.spawn(); // fails, the pool is full
+    /// 
+    /// swarm.tick();
+    /// swarm.tick();
+    /// swarm.kill(&a);
+    /// 
+    /// let stats = swarm.stats();
+    /// assert_eq!(stats.spawns, 2);
+    /// assert_eq!(stats.kills, 1);
+    /// assert_eq!(stats.len, 1);
+    /// assert_eq!(stats.peak_len, 2);
+    /// assert_eq!(stats.failed_spawns, 1);
+    /// assert_eq!(stats.free_len, 1);
+    /// assert_eq!(stats.average_lifetime, 2.0);
+    /// ```
+    pub fn stats(&self) -> SwarmStats {
+        let c = &self.counters;
+        SwarmStats {
+            spawns: c.spawns,
+            kills: c.kills,
+            len: self.len,
+            peak_len: c.peak_len,
+            failed_spawns: c.failed_spawns,
+            free_len: self.free.len(),
+            average_lifetime: if c.kills > 0 { c.lifetimes as f64 / c.kills as f64 } else { 0.0 },
+        }
+    }
+
+    /// Resets the usage statistics, the peak length starts at the current length
+    pub fn reset_stats(&mut self) {
+        self.counters = Counters { peak_len: self.len, ..Counters::default() };
+    }
+
+    /// Verify that the spawn administration is consistent: every spawn points to its own
+    /// pool position, exactly the first `count()` spawns are active, the free list holds 
+    /// the killed spawns that follow them, and groups only hold active spawns.
+    /// 
+    /// With the `integrity-checks` feature enabled, debug builds call this after every
+    /// spawn, kill, tick and update, and panic on the first error.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u8, _>::new(10, ());
+    /// let spawn = swarm.spawn().unwrap();
+    /// swarm.spawn();
+    /// swarm.kill(&spawn);
+    /// swarm.kill(&spawn);
+    /// 
+    /// assert_eq!(swarm.check_integrity(), Ok(()));
+    /// ```
+    pub fn check_integrity(&self) -> Result<(), IntegrityError> {
+        let (len, max) = (self.len, self.max);
+        let used = len + self.free.len();
+
+        if used > max {
+            return Err(IntegrityError::Overflow { len: used, max });
+        }
+        for (name, size) in [("spawns", self.spawns.len()), ("order", self.order.len()), ("by_id", self.by_id.len())] {
+            if size != max {
+                return Err(IntegrityError::BufferSize { name, size, max });
+            }
+        }
+        if self.pool.len() != used {
+            return Err(IntegrityError::Constructed { constructed: self.pool.len(), used });
+        }
+
+        for (pos, spawn) in self.spawns.iter().enumerate() {
+            let tag = spawn.0.borrow();
+            if tag.pos != pos {
+                return Err(IntegrityError::Position { pos, points_to: tag.pos });
+            }
+            if !self.by_id.get(tag.id).is_some_and(|s| Rc::ptr_eq(&s.0, &spawn.0)) {
+                return Err(IntegrityError::Identity { pos, id: tag.id });
+            }
+            if tag.active != (pos < len) {
+                return Err(IntegrityError::Active { pos, active: tag.active });
+            }
+        }
+
+        // the last killed spawn is on top of the free list and directly follows the spawned range
+        for (index, spawn) in self.free.iter().enumerate() {
+            let pos = used - 1 - index;
+            if !Rc::ptr_eq(&spawn.0, &self.spawns[pos].0) {
+                return Err(IntegrityError::Free { index, pos });
+            }
+        }
+
+        let mut memberships = vec![0; max];
+        for group in &self.groups {
+            for member in &group.members {
+                let id = member.id();
+                if !member.active() || !self.by_id.get(id).is_some_and(|s| Rc::ptr_eq(&s.0, &member.0)) {
+                    return Err(IntegrityError::Group { group_id: group.group_id, id });
+                }
+                memberships[id] += 1;
+            }
+        }
+        for (id, spawn) in self.by_id.iter().enumerate() {
+            let counted = spawn.0.borrow().groups;
+            if counted != memberships[id] {
+                return Err(IntegrityError::Membership { id, counted, found: memberships[id] });
+            }
+        }
+        Ok(())
+    }
+
+    // runs check_integrity() after mutations, when enabled by the integrity-checks feature
+    #[inline]
+    fn debug_check(&self) {
+        #[cfg(all(debug_assertions, feature = "integrity-checks"))]
+        if let Err(error) = self.check_integrity() {
+            panic!("swarm integrity violated: {}", error);
+        }
+    }
+
+    /// Returns all spawned objects as one contiguous slice, spawned objects are always
+    /// stored at the front of the pool. The slice index of an object is its object position.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    ///
+    /// let mut swarm = Swarm::<u32, _>::new(10, ());
+    /// swarm.populate(&[1, 2, 3]);
+    ///
+    /// assert_eq!(swarm.active_slice(), &[1, 2, 3]);
+    /// assert_eq!(swarm.active_slice().iter().sum::<u32>(), 6);
+    /// ```
+    pub fn active_slice(&self) -> &[ItemType] {
+        &self.pool[..self.len]
+    }
+
+    /// Returns all spawned objects as one contiguous mutable slice, see active_slice().
+    ///
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    ///
+    /// let mut swarm = Swarm::<f32, _>::new(10, ());
+    /// swarm.populate(&[1.0, 2.0, 3.0]);
+    ///
+    /// for value in swarm.active_slice_mut() {
+    ///     *value *= 2.0;
+    /// }
+    /// assert_eq!(swarm.active_slice(), &[2.0, 4.0, 6.0]);
+    /// ```
+    pub fn active_slice_mut(&mut self) -> &mut [ItemType] {
+        self.mark_active();
+        &mut self.pool[..self.len]
+    }
     
 
     // update iterators
@@ -481,7 +1325,9 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// Loops through all spawned instances and returns them via a callback
     /// handler. The callback handler is supplied with a mutable reference of these
     /// instances so that the object data of each looped instance can be changed.
+    #[allow(clippy::op_ref)]
     pub fn enumerate(&mut self, handler: EnumerateHandler<ItemType>) {
+        self.mark_active();
         let len = self.len;
         let mut i = 0;
 
@@ -519,7 +1365,9 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// assert_eq!(swarm.fetch_ref(&spawn1).value, 42);
     /// assert_eq!(swarm.fetch_ref(&spawn2).value, 42);
     /// ```
+    #[allow(clippy::op_ref)]
     pub fn for_each(&mut self, handler: ForEachHandler<ItemType>) {
+        self.mark_active();
         let len = self.len;
         let mut i = 0;
 
@@ -529,9 +1377,49 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
         }
     }
     
+    /// Loops through all spawned instances in chunks of `size` objects and returns each
+    /// chunk as a mutable slice via a callback handler. The last chunk holds fewer objects 
+    /// if the number of spawns is not a multiple of `size`.
+    /// 
+    /// Tight loops over a slice are easier for the compiler to vectorize than loops that
+    /// call a handler per object.
+    /// 
+    /// Panics if `size` is 0.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    ///
+    /// let mut swarm = Swarm::<f32, _>::new(10, ());
+    /// swarm.populate(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+    ///
+    /// swarm.for_each_chunk(2, |chunk| {
+    ///     for value in chunk.iter_mut() { *value += 1.0; }
+    /// });
+    /// assert_eq!(swarm.active_slice(), &[2.0, 3.0, 4.0, 5.0, 6.0]);
+    /// ```
+    pub fn for_each_chunk(&mut self, size: usize, handler: ChunkHandler<ItemType>) {
+        self.mark_active();
+        for chunk in self.pool[..self.len].chunks_mut(size) {
+            handler(chunk);
+        }
+    }
+
+    /// Loops through all spawns that are a member of a group and returns them via a 
+    /// callback handler, see `add_to_group()`. 
+    pub fn for_each_in_group(&mut self, group_id: GroupId, handler: ForEachHandler<ItemType>) {
+        if let Some(group) = self.groups.iter().find(|g| g.group_id == group_id) {
+            for spawn in &group.members {
+                if self.tracking { spawn.0.borrow_mut().changed = Some(self.ticks); }
+                handler(&mut self.pool[spawn.pos()]);
+            }
+        }
+    }
+    
     /// Loops through all spawned instances and returns their object position via a 
     /// callback handler. The callback handler also hands out a mutable reference to
-    /// the object pool and the swarm properties object.
+    /// the spawned objects, see active_slice_mut(), and the swarm properties object.
     /// 
     /// NOTE: This methode is quite fast (faster than the foreach or update methodes), but
     /// at the cost that is uses object positions. When an object is killed, the order 
@@ -639,12 +1527,14 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// assert_eq!(swarm.fetch_ref(&s_john).value, 1);
     /// assert_eq!(swarm.fetch_ref(&s_cristy).value, 2);
     /// ```
+    #[allow(clippy::op_ref)]
     pub fn for_all(&mut self, handler: ForAllHandler<ItemType, Properties>) {
+        self.mark_active();
         let len = self.len;
         let mut i = 0;
 
         while &i < &len {
-            handler(&i, &mut self.pool, &mut self.properties);
+            handler(&i, &mut self.pool[..len], &mut self.properties);
             i += 1;
         }
     }
@@ -710,16 +1600,436 @@ impl<ItemType: Default + Clone, Properties> Swarm<ItemType, Properties> {
     /// assert_eq!(swarm.fetch_ref(&s_cristy).value, 2);
     /// ```
     pub fn update(&mut self, handler: UpdateHandler<ItemType, Properties>) {
+        self.kill_dropped();
+        self.run_update(handler, &[], &[]);
+    }
+
+    // the update loop, `previous` and `started` hold the state of the pool before the loop 
+    // when it is double buffered, see update_buffered()
+    fn run_update(&mut self, handler: UpdateHandler<ItemType, Properties>, 
+        previous: &[ItemType], started: &[Option<(ObjectPosition, u32)>]) {
+        let len = self.len;
+
+        // remember which spawns have to be visited, killing moves spawns around
+        for (pos, spawn) in self.spawns[..len].iter().enumerate() {
+            let mut tag = spawn.0.borrow_mut();
+            tag.pending = true;
+            self.order[pos] = tag.id;
+        }
+
+        let (ctl, by_id, swarm_len) = self.split_control();
+        let mut guard = UpdateGuard { ctl, len: swarm_len };
+        let ctl = &mut guard.ctl;
+        ctl.previous = previous;
+        ctl.started = started;
+
         let mut i = 0;
+        while i < len {
+            let visit = {
+                let mut tag = by_id[ctl.order[i]].0.borrow_mut();
+                let pending = tag.pending;
+                tag.pending = false;
+                if pending { Some(tag.pos) } else { None }
+            };
+
+            // spawns killed before their turn are skipped
+            if let Some(pos) = visit {
+                ctl.pos = pos;
+                handler(ctl);
+            }
+            i += 1;
+        }
+        drop(guard);
+        self.debug_check();
+    }
+}
+
+// writes the length of the control back into the swarm when the update loop ends,
+// even when a callback handler panics, so the swarm stays consistent
+#[cfg(feature = "alloc")]
+struct UpdateGuard<'a, ItemType, Properties> {
+    ctl: SwarmControl<'a, ItemType, Properties>,
+    len: &'a mut usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<ItemType, Properties> Drop for UpdateGuard<'_, ItemType, Properties> {
+    fn drop(&mut self) {
+        *self.len = self.ctl.len;
+    }
+}
+
+/// Lists the spawned objects by their spawn id, and the ids of the free slots in the 
+/// order they will be recycled.
+#[cfg(feature = "alloc")]
+impl<ItemType: fmt::Debug, Properties> fmt::Debug for Swarm<ItemType, Properties> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Swarm")
+            .field("len", &self.len)
+            .field("capacity", &self.max)
+            .field("ticks", &self.ticks)
+            .field("active", &ActiveSlots(&self.spawns[..self.len], &self.pool[..self.len]))
+            .field("free", &FreeSlots(&self.free))
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct ActiveSlots<'a, ItemType>(&'a [Spawn], &'a [ItemType]);
+
+#[cfg(feature = "alloc")]
+impl<ItemType: fmt::Debug> fmt::Debug for ActiveSlots<'_, ItemType> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter().map(Spawn::id).zip(self.1)).finish()
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct FreeSlots<'a>(&'a [Spawn]);
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for FreeSlots<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.iter().rev().map(Spawn::id)).finish()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<ItemType: Clone, Properties> Swarm<ItemType, Properties> {
+
+    /// Create a new spawn for every item in the `items` list and gives
+    /// it that value.
+    /// 
+    /// # Example
+    /// ```
+    ///     extern crate swarm_pool;
+    ///     use swarm_pool::{ Swarm, Spawn };
+    /// 
+    ///     let mut swarm = Swarm::<u8, _>::new(10, ());
+    ///     swarm.populate(&[5, 4, 3, 2, 1]);
+    ///     
+    ///     assert_eq!(swarm.count(), 5);
+    ///     assert_eq!(*swarm.fetch_raw(&0), 5);
+    /// ```
+    pub fn populate(&mut self, items: &[ItemType]) {
+        let count = self.unrecorded(|swarm| {
+            items.iter().filter(|item| swarm.spawn_with((*item).clone()).is_some()).count()
+        });
+        self.record(Op::Populate { count });
+        self.record_values(&items[..count]);
+    }
+
+    /// Loops through all spawned instances like `for_all()` does, but double buffered.
+    /// The callback handler reads from an immutable copy of the pool as it was before 
+    /// the loop started, and writes into the next version of the object at the current 
+    /// object position. The buffers are swapped at the end of the loop.
+    /// 
+    /// Because no object can see what its neighbours wrote during this loop, the results
+    /// do not depend on the order of the spawns. This makes cellular automata and flocking 
+    /// deterministic, even after spawns were killed and moved around.
+    /// 
+    /// NOTE: the next version of an object starts out as a copy of its previous version.
+    /// The second buffer is allocated the first time this methode is called, after that it 
+    /// is reused and only grows by the slots constructed since the previous call.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    ///
+    /// // every cell becomes the sum of itself and its neighbours
+    /// let mut swarm = Swarm::<usize, _>::new(10, ());
+    /// swarm.populate(&[1, 2, 3]);
+    /// 
+    /// swarm.for_all_buffered(|pos, prev, next, _props| {
+    ///     let left = if *pos > 0 { prev[*pos - 1] } else { 0 };
+    ///     let right = if *pos + 1 < prev.len() { prev[*pos + 1] } else { 0 };
+    ///     *next = left + prev[*pos] + right;
+    /// });
+    ///
+    /// assert_eq!(*swarm.fetch_raw(&0), 3);
+    /// assert_eq!(*swarm.fetch_raw(&1), 6);
+    /// assert_eq!(*swarm.fetch_raw(&2), 5);
+    /// ```
+    pub fn for_all_buffered(&mut self, handler: BufferedHandler<ItemType, Properties>) {
+        self.mark_active();
+        self.fill_back();
         let len = self.len;
-        let mut ctl = self.control();
 
-        while &i < &len {
-            ctl.pos = ctl.order[*&i];
-            handler(&mut ctl);
-            ctl.order[*&i] = i; 
+        let mut i = 0;
+        while i < len {
+            handler(&i, &self.pool[..len], &mut self.back[i], &mut self.properties);
             i += 1;
         }
-        self.len = ctl.len;
+        core::mem::swap(&mut self.pool, &mut self.back);
+    }
+
+    /// Loops through all spawned instances like `update()` does, but double buffered.
+    /// Next to editing the pool through the `SwarmControl`, the callback handler can read 
+    /// every object as it was before the loop started with `SwarmControl.previous()`.
+    /// 
+    /// When every object only reads the previous versions of its neighbours, the results
+    /// do not depend on the order of the spawns, or on spawns killed and spawned during 
+    /// the loop. 
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::{ Swarm, Spawn };
+    ///
+    /// // every object follows its leader, the leaders pass on their previous value
+    /// let mut swarm = Swarm::<(usize, Option<Spawn>), _>::new(10, ());
+    /// let first = swarm.spawn_with((1, None)).unwrap();
+    /// let second = swarm.spawn_with((2, Some(first.mirror()))).unwrap();
+    /// let third = swarm.spawn_with((3, Some(second.mirror()))).unwrap();
+    /// 
+    /// swarm.update_buffered(|ctl| {
+    ///     let leader = ctl.target().1.as_ref().map(Spawn::mirror);
+    ///     if let Some(value) = leader.and_then(|l| ctl.previous(&l).map(|p| p.0)) {
+    ///         ctl.target().0 = value;
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(swarm.fetch_ref(&second).0, 1);
+    /// assert_eq!(swarm.fetch_ref(&third).0, 2);
+    /// ```
+    pub fn update_buffered(&mut self, handler: UpdateHandler<ItemType, Properties>) {
+        self.kill_dropped();
+        self.fill_back();
+
+        // remember where every spawn was, so the previous versions can be found by spawn
+        let mut started = core::mem::take(&mut self.started);
+        started.clear();
+        started.resize(self.by_id.len(), None);
+        for spawn in &self.spawns[..self.len] {
+            let tag = spawn.0.borrow();
+            started[tag.id] = Some((tag.pos, tag.gen));
+        }
+
+        let back = core::mem::take(&mut self.back);
+        self.run_update(handler, &back[..self.len], &started);
+        self.back = back;
+        self.started = started;
+    }
+
+    // copies every constructed slot into the back buffer, killed objects included, so a 
+    // slot recycled after the buffers are swapped still holds its last object.
+    // The buffer is reused, only slots constructed since the last call are cloned as a whole.
+    fn fill_back(&mut self) {
+        let size = self.pool.len();
+        self.back.truncate(size);
+
+        let reused = self.back.len();
+        self.back.clone_from_slice(&self.pool[..reused]);
+        self.back.extend_from_slice(&self.pool[reused..]);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<ItemType: Clone, Properties: Clone> Swarm<ItemType, Properties> {
+
+    /// Returns an independent copy of the swarm, and a SpawnMap that translates the
+    /// spawns of this swarm to those of the copy. Spawns keep their id in the copy,
+    /// but killing or spawning in one swarm does not affect the other.
+    /// 
+    /// Spawns stored in the properties or pool objects are copied as they are, and
+    /// still point to this swarm. Use the map to translate them.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::{ Swarm, Spawn };
+    /// 
+    /// let mut swarm = Swarm::<u8, Option<Spawn>>::new(10, None);
+    /// let player = swarm.spawn_with(100).unwrap();
+    /// swarm.properties = Some(player.mirror());
+    /// 
+    /// // look ahead in a forked simulation
+    /// let (mut fork, map) = swarm.clone_with_map();
+    /// fork.properties = fork.properties.and_then(|p| map.translate(&p));
+    /// let fork_player = map.translate(&player).unwrap();
+    /// 
+    /// fork.kill(&fork_player);
+    /// assert!(player.active());
+    /// assert!(!fork.properties.unwrap().active());
+    /// assert_eq!(*swarm.fetch_ref(&player), 100);
+    /// ```
+    pub fn clone_with_map(&self) -> (Self, SpawnMap) {
+        // fresh tags with the same ids, positions and state
+        let by_id: Vec<Spawn> = self.by_id.iter()
+            .map(|s| Spawn(Rc::new(RefCell::new(*s.0.borrow()))))
+            .collect();
+        let remap = |s: &Spawn| by_id[s.id()].mirror();
+
+        // kills of dropped guards that are still queued, apply to the clone as well
+        let dropped = self.dropped.borrow().iter().map(|(s, gen)| (remap(s), *gen)).collect();
+
+        let swarm = Swarm {
+            pool: self.pool.clone(),
+            back: Vec::new(),
+            spawns: self.spawns.iter().map(remap).collect(),
+            free: self.free.iter().map(remap).collect(),
+            len: self.len,
+            max: self.max,
+            order: self.order.clone(),
+            by_id: by_id.iter().map(Spawn::mirror).collect(),
+            started: Vec::new(),
+            factories: self.factories.iter().map(|f| Factory { type_def: f.type_def, methode: f.methode }).collect(),
+            groups: self.groups.iter()
+                .map(|g| Group { group_id: g.group_id, members: g.members.iter().map(remap).collect() })
+                .collect(),
+            expire: self.expire,
+            constructor: self.constructor,
+            counters: self.counters,
+            ticks: self.ticks,
+            dropped: Rc::new(RefCell::new(dropped)),
+            recorder: None,
+            encoder: None,
+            tracking: self.tracking,
+            properties: self.properties.clone(),
+        };
+        let map = SpawnMap {
+            from: self.by_id.iter().map(|s| Rc::downgrade(&s.0)).collect(),
+            to: by_id,
+        };
+        (swarm, map)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<ItemType: Clone, Properties: Clone> Swarm<ItemType, Properties> {
+
+    /// Save the full state of the swarm, including its properties, so it can be rolled
+    /// back to later on by `restore_state()`. Use `save_state_into()` to save every tick
+    /// without allocating, when the pool objects are Copy.
+    pub fn save_state(&self) -> SwarmState<ItemType, Properties> {
+        let mut state = SwarmState {
+            pool: self.pool.clone(),
+            tags: Vec::with_capacity(self.max),
+            spawns: Vec::with_capacity(self.max),
+            free: Vec::with_capacity(self.max),
+            groups: Vec::new(),
+            members: Vec::new(),
+            len: self.len,
+            counters: self.counters,
+            ticks: self.ticks,
+            properties: self.properties.clone(),
+        };
+        self.save_spawns(&mut state);
+        state
+    }
+
+    // stores the spawn administration by spawn id, reusing the buffers of the state
+    fn save_spawns(&self, state: &mut SwarmState<ItemType, Properties>) {
+        state.tags.clear();
+        state.tags.extend(self.by_id.iter().map(|s| *s.0.borrow()));
+        state.spawns.clear();
+        state.spawns.extend(self.spawns.iter().map(Spawn::id));
+        state.free.clear();
+        state.free.extend(self.free.iter().map(Spawn::id));
+        state.groups.clear();
+        state.members.clear();
+        for group in &self.groups {
+            state.groups.push((group.group_id, group.members.len()));
+            state.members.extend(group.members.iter().map(Spawn::id));
+        }
+    }
+
+    /// Roll the swarm back to a state saved by `save_state()`.
+    /// 
+    /// Outstanding spawns are not replaced, they are set to the state they were in, so
+    /// every spawn points to the same object it pointed to when the state was saved.
+    /// Spawns of objects that were spawned after the state was saved become inactive, 
+    /// and point to their object again when a replay spawns it the same way.
+    /// 
+    /// # Panics
+    /// If the capacity of the swarm changed since the state was saved, see `shrink_to()`.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<u32, _>::new(10, ());
+    /// let player = swarm.spawn_with(100).unwrap();
+    /// let state = swarm.save_state();
+    /// 
+    /// // frames that are rolled back
+    /// let bullet = swarm.spawn_with(5).unwrap();
+    /// swarm.kill(&player);
+    /// 
+    /// swarm.restore_state(&state);
+    /// assert!(player.active());
+    /// assert!(!bullet.active());
+    /// assert_eq!(*swarm.fetch_ref(&player), 100);
+    /// ```
+    pub fn restore_state(&mut self, state: &SwarmState<ItemType, Properties>) {
+        assert_eq!(state.tags.len(), self.max, "the swarm capacity changed since the state was saved");
+
+        for (spawn, tag) in self.by_id.iter().zip(&state.tags) {
+            *spawn.0.borrow_mut() = *tag;
+        }
+        let by_id = &self.by_id;
+        self.spawns.clear();
+        self.spawns.extend(state.spawns.iter().map(|id| by_id[*id].mirror()));
+        self.free.clear();
+        self.free.extend(state.free.iter().map(|id| by_id[*id].mirror()));
+
+        let mut members = state.members.iter();
+        self.groups.clear();
+        for (group_id, count) in &state.groups {
+            let members = members.by_ref().take(*count).map(|id| by_id[*id].mirror()).collect();
+            self.groups.push(Group { group_id: *group_id, members });
+        }
+
+        self.pool.clone_from(&state.pool);
+        self.len = state.len;
+        self.counters = state.counters;
+        self.ticks = state.ticks;
+        self.properties.clone_from(&state.properties);
+        self.debug_check();
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<ItemType: Copy, Properties: Clone> Swarm<ItemType, Properties> {
+
+    /// Save the full state of the swarm into a `state` created earlier by `save_state()`.
+    /// The buffers of the state are reused and pool objects are copied in bulk, so saving 
+    /// the state every tick does not allocate once the buffers are large enough.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::Swarm;
+    /// 
+    /// let mut swarm = Swarm::<(f32, f32), _>::new(100, ());
+    /// let mut state = swarm.save_state();
+    /// 
+    /// for frame in 0..10 {
+    ///     swarm.spawn_with((frame as f32, 0.0));
+    ///     swarm.save_state_into(&mut state);
+    /// }
+    /// 
+    /// swarm.kill_all();
+    /// swarm.restore_state(&state);
+    /// assert_eq!(swarm.count(), 10);
+    /// ```
+    pub fn save_state_into(&self, state: &mut SwarmState<ItemType, Properties>) {
+        state.pool.clear();
+        state.pool.extend_from_slice(&self.pool);
+        state.len = self.len;
+        state.counters = self.counters;
+        state.ticks = self.ticks;
+        state.properties.clone_from(&self.properties);
+        self.save_spawns(state);
+    }
+}
+
+/// Clones the swarm with fresh spawns, see Swarm.clone_with_map()
+#[cfg(feature = "alloc")]
+impl<ItemType: Clone, Properties: Clone> Clone for Swarm<ItemType, Properties> {
+    fn clone(&self) -> Self {
+        self.clone_with_map().0
     }
 }
@@ -0,0 +1,224 @@
+//! Recording the spawn and kill operations of a swarm, so they can be replayed.
+//!
+//! A recording Swarm logs every spawn, kill, kill_all, populate, set_ttl and tick, including the
+//! ones made through SwarmControl during update(), and the kills and spawns of applied deltas. Replaying the log on a swarm that was
+//! restored to the state the recording started at, rebuilds the same spawns at the same
+//! positions.
+//!
+//! The values passed to `spawn_with()` and `populate()` are only recorded when recording was 
+//! started by `Swarm::start_recording_with()`, which encodes them through a callback handler. 
+//! `Swarm::replay_with()` decodes them again, so the replayed pool holds the same objects.
+//! Changes made to the pool objects after they were spawned, for example in update(), are
+//! never recorded.
+//!
+//! Example
+//! ```
+//! extern crate swarm_pool;
+//! use swarm_pool::Swarm;
+//! use swarm_pool::record::OpLog;
+//!
+//! let mut swarm = Swarm::<u8, ()>::new(10, ());
+//! let start = swarm.save_state();
+//! swarm.start_recording();
+//!
+//! swarm.spawn();
+//! swarm.spawn();
+//! swarm.update(|ctl| if ctl.target_spawn().id() == 0 { ctl.kill_current(); });
+//!
+//! // store the log with the bug report, and replay it later on
+//! let bytes = swarm.stop_recording().unwrap().to_bytes().unwrap();
+//! let mut replayed = Swarm::<u8, ()>::new(10, ());
+//! replayed.restore_state(&start);
+//! replayed.replay(&OpLog::from_bytes(&bytes).unwrap()).unwrap();
+//!
+//! assert_eq!(replayed.count(), 1);
+//! assert_eq!(replayed.fetch_spawn(&0).id(), swarm.fetch_spawn(&0).id());
+//! assert!(!replayed.fetch_spawn(&1).active());
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::{ convert::TryFrom, fmt };
+
+use super::types::*;
+
+// the first bytes of an encoded log, followed by the format version
+const MAGIC: &[u8; 4] = b"SWRL";
+const VERSION: u8 = 1;
+
+/// A recorded swarm operation, spawned and killed spawns are stored by their id
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Swarm.spawn() or SwarmControl.spawn()
+    Spawn { id: SpawnId },
+    /// Swarm.spawn_with() or SwarmControl.spawn_with(), its value is in `OpLog.values()`
+    /// when it was recorded by `Swarm.start_recording_with()`
+    SpawnWith { id: SpawnId },
+    /// Swarm.spawn_type()
+    SpawnType { type_def: usize, id: SpawnId },
+    /// A spawn of Swarm.apply_delta() with its spawn id and generation. Its value is in 
+    /// `OpLog.values()` when it was recorded by `Swarm.start_recording_with()`
+    SpawnAs { id: SpawnId, gen: u32 },
+    /// Swarm.kill() or SwarmControl.kill() of an active spawn, including dropped SpawnGuards
+    Kill { id: SpawnId },
+    /// Swarm.set_ttl() or SwarmControl.set_ttl() of an active spawn, including spawn_with_ttl()
+    SetTtl { id: SpawnId, ttl: Option<usize> },
+    /// Swarm.kill_all()
+    KillAll,
+    /// Swarm.populate(), with the number of spawned items, their values are in 
+    /// `OpLog.values()` when they were recorded by `Swarm.start_recording_with()`
+    Populate { count: usize },
+    /// Swarm.tick(), spawns that expire are not recorded separately
+    Tick,
+}
+
+/// A log of swarm operations, see the module documentation
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct OpLog {
+    ops: Vec<Op>,
+    values: Vec<Vec<u8>>,
+}
+
+impl OpLog {
+    /// Create an empty log
+    pub fn new() -> Self { OpLog::default() }
+
+    /// Returns the recorded operations, oldest first
+    pub fn ops(&self) -> &[Op] { &self.ops }
+
+    /// Returns the number of recorded operations
+    pub fn len(&self) -> usize { self.ops.len() }
+
+    /// Returns true if no operations were recorded
+    pub fn is_empty(&self) -> bool { self.ops.is_empty() }
+
+    /// Returns the encoded values of the SpawnWith and Populate operations, in the order
+    /// they were spawned. Empty unless the log was recorded by `Swarm.start_recording_with()`.
+    pub fn values(&self) -> &[Vec<u8>] { &self.values }
+
+    pub(crate) fn push(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    pub(crate) fn push_value(&mut self, value: Vec<u8>) {
+        self.values.push(value);
+    }
+
+    /// Encode the log in a compact binary format: a header, the number of values followed
+    /// by every value as its length and bytes, and then one opcode byte per operation and
+    /// its arguments. Numbers are stored as little endian u32 values.
+    /// 
+    /// Returns None if a number, such as a spawn id or a time to live, does not fit in a u32.
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 5 + self.ops.len() * 5);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+
+        write_u32(&mut bytes, self.values.len())?;
+        for value in &self.values {
+            write_u32(&mut bytes, value.len())?;
+            bytes.extend_from_slice(value);
+        }
+
+        for op in &self.ops {
+            let (code, args): (u8, &[usize]) = match op {
+                Op::Spawn { id } => (0, &[*id]),
+                Op::SpawnWith { id } => (1, &[*id]),
+                Op::SpawnType { type_def, id } => (2, &[*type_def, *id]),
+                Op::Kill { id } => (3, &[*id]),
+                Op::KillAll => (4, &[]),
+                Op::Populate { count } => (5, &[*count]),
+                Op::Tick => (6, &[]),
+                Op::SetTtl { id, ttl: Some(ttl) } => (7, &[*id, *ttl]),
+                Op::SetTtl { id, ttl: None } => (8, &[*id]),
+                Op::SpawnAs { id, gen } => (9, &[*id, *gen as usize]),
+            };
+            bytes.push(code);
+            for arg in args {
+                write_u32(&mut bytes, *arg)?;
+            }
+        }
+        Some(bytes)
+    }
+
+    /// Decode a log created by `to_bytes()`.
+    /// Returns None if the bytes are not a log, or were truncated or corrupted.
+    pub fn from_bytes(bytes: &[u8]) -> Option<OpLog> {
+        let body = bytes.strip_prefix(MAGIC)?.strip_prefix(&[VERSION])?;
+        let mut reader = Reader(body);
+        let mut log = OpLog::new();
+
+        for _ in 0..reader.arg()? {
+            let len = reader.arg()?;
+            let value = reader.bytes(len)?;
+            log.push_value(value.to_vec());
+        }
+
+        while let Some(code) = reader.byte() {
+            let op = match code {
+                0 => Op::Spawn { id: reader.arg()? },
+                1 => Op::SpawnWith { id: reader.arg()? },
+                2 => Op::SpawnType { type_def: reader.arg()?, id: reader.arg()? },
+                3 => Op::Kill { id: reader.arg()? },
+                4 => Op::KillAll,
+                5 => Op::Populate { count: reader.arg()? },
+                6 => Op::Tick,
+                7 => Op::SetTtl { id: reader.arg()?, ttl: Some(reader.arg()?) },
+                8 => Op::SetTtl { id: reader.arg()?, ttl: None },
+                9 => Op::SpawnAs { id: reader.arg()?, gen: reader.arg()? as u32 },
+                _ => return None,
+            };
+            log.push(op);
+        }
+        Some(log)
+    }
+}
+
+// reads opcodes and arguments from the front of an encoded log
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let (first, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(*first)
+    }
+
+    fn arg(&mut self) -> Option<usize> {
+        let arg = self.bytes(4)?;
+        Some(u32::from_le_bytes([arg[0], arg[1], arg[2], arg[3]]) as usize)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        if len > self.0.len() { return None; }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+}
+
+// appends a number as a little endian u32, None if it does not fit
+fn write_u32(bytes: &mut Vec<u8>, value: usize) -> Option<()> {
+    let value = u32::try_from(value).ok()?;
+    bytes.extend_from_slice(&value.to_le_bytes());
+    Some(())
+}
+
+/// Returned by Swarm.replay() when the swarm does not behave like the recorded one,
+/// usually because it was not restored to the state the recording started at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReplayError {
+    /// The index of the operation that could not be replayed
+    pub index: usize,
+    /// The operation that could not be replayed
+    pub op: Op,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "replaying operation {} ({:?}) diverged from the recording", self.index, self.op)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReplayError {}
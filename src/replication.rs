@@ -0,0 +1,251 @@
+//! Replicating a swarm over the network by sending the changes since the last acknowledged state.
+//!
+//! The server saves the state it knows the client has, see Swarm.save_state(), and creates a
+//! SwarmDelta against it with `Swarm.delta()`. A delta holds the spawned objects, the ids of
+//! killed spawns, and a patch for every object that changed, created by the pool object's Diff
+//! implementation. The client applies the delta with `Swarm.apply_delta()`, which spawns the
+//! objects with the same spawn ids, so spawns can be shared between server and client by id,
+//! or by `Spawn.to_bits()`.
+//!
+//! The time to live of spawns is not replicated, spawned objects have no time to live on the 
+//! client. Spawns that expire on the server are replicated as kills. A recording client logs 
+//! the kills and spawns of applied deltas, but not the patches, see the `record` module.
+//!
+//! Example
+//! ```
+//! extern crate swarm_pool;
+//! use swarm_pool::Swarm;
+//! use swarm_pool::replication::Diff;
+//!
+//! #[derive(Default, Clone, PartialEq, Debug)]
+//! pub struct Unit { x: f32, hp: u32 }
+//!
+//! // only the changed fields are sent
+//! impl Diff for Unit {
+//!     type Patch = (Option<f32>, Option<u32>);
+//!
+//!     fn diff(&self, base: &Self) -> Option<Self::Patch> {
+//!         let patch = ((self.x != base.x).then_some(self.x), (self.hp != base.hp).then_some(self.hp));
+//!         if patch == (None, None) { None } else { Some(patch) }
+//!     }
+//!
+//!     fn apply(&mut self, patch: &Self::Patch) {
+//!         if let Some(x) = patch.0 { self.x = x; }
+//!         if let Some(hp) = patch.1 { self.hp = hp; }
+//!     }
+//! }
+//!
+//! let mut server = Swarm::<Unit, ()>::new(10, ());
+//! let mut client = Swarm::<Unit, ()>::new(10, ());
+//! let mut acked = server.save_state();
+//!
+//! let tank = server.spawn_with(Unit { x: 0.0, hp: 100 }).unwrap();
+//! let scout = server.spawn_with(Unit { x: 5.0, hp: 20 }).unwrap();
+//! client.apply_delta(&server.delta(&acked)).unwrap();
+//! acked = server.save_state();
+//!
+//! server.fetch(&tank).x = 1.0;
+//! server.kill(&scout);
+//! let delta = server.delta(&acked);
+//! assert_eq!(delta.changed, vec![(tank.id(), (Some(1.0), None))]);
+//! assert_eq!(delta.killed, vec![scout.id()]);
+//!
+//! client.apply_delta(&delta).unwrap();
+//! let client_tank = client.spawn_by_id(tank.id()).unwrap();
+//! assert_eq!(client.fetch_ref(&client_tank), &Unit { x: 1.0, hp: 100 });
+//! assert_eq!(client.count(), 1);
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::{ vec, vec::Vec };
+use core::{ fmt, mem, slice };
+
+use super::record::Op;
+use super::types::*;
+use super::Swarm;
+
+/// Compares a pool object to an older version of itself, field by field.
+pub trait Diff {
+    /// The changes between two versions of an object
+    type Patch;
+
+    /// Returns the patch that turns `base` into `self`, or None if nothing changed
+    fn diff(&self, base: &Self) -> Option<Self::Patch>;
+
+    /// Applies a patch created by `diff()`
+    fn apply(&mut self, patch: &Self::Patch);
+}
+
+/// The changes of a swarm since an earlier state, see the module documentation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwarmDelta<ItemType: Diff> {
+    /// The spawn id, generation and object of every spawned object
+    pub spawned: Vec<(SpawnId, u32, ItemType)>,
+    /// The ids of killed spawns
+    pub killed: Vec<SpawnId>,
+    /// The spawn id and patch of every changed object
+    pub changed: Vec<(SpawnId, ItemType::Patch)>,
+}
+
+impl<ItemType: Diff> SwarmDelta<ItemType> {
+    /// Returns true if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.spawned.is_empty() && self.killed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Returned by Swarm.apply_delta() when a delta does not fit the swarm, because the
+/// swarm is not in the state the delta was created against, or the delta is malformed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeltaError {
+    /// The spawn id that does not match the delta
+    pub id: SpawnId,
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the delta does not match the state of spawn {}", self.id)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DeltaError {}
+
+impl<ItemType: Diff + Clone, Properties> Swarm<ItemType, Properties> {
+
+    /// Returns the changes of this swarm since the `base` state was saved. Spawns that
+    /// were killed and spawned again since then are sent as a kill and a spawn.
+    pub fn delta(&self, base: &SwarmState<ItemType, Properties>) -> SwarmDelta<ItemType> {
+        let mut delta = SwarmDelta { spawned: Vec::new(), killed: Vec::new(), changed: Vec::new() };
+
+        for (id, spawn) in self.by_id.iter().enumerate() {
+            let tag = *spawn.0.borrow();
+            let base_tag = base.tags.get(id).filter(|t| t.active);
+
+            match base_tag {
+                Some(base_tag) if tag.active && base_tag.gen == tag.gen => {
+                    if let Some(patch) = self.pool[tag.pos].diff(&base.pool[base_tag.pos]) {
+                        delta.changed.push((id, patch));
+                    }
+                },
+                _ => {
+                    if base_tag.is_some() { delta.killed.push(id); }
+                    if tag.active { delta.spawned.push((id, tag.gen, self.pool[tag.pos].clone())); }
+                },
+            }
+        }
+        delta
+    }
+
+    /// Apply a delta created by `Swarm.delta()` on the server. Killed spawns are killed,
+    /// spawned objects are spawned with the same spawn id and generation, and changed
+    /// objects are patched. Spawned objects have no time to live, see the module documentation.
+    ///
+    /// The delta is checked before it is applied. If it does not match the swarm, or lists 
+    /// a spawn id twice in the same list, nothing changes and a DeltaError is returned.
+    pub fn apply_delta(&mut self, delta: &SwarmDelta<ItemType>) -> Result<(), DeltaError> {
+        self.kill_dropped();
+        self.check_delta(delta)?;
+
+        for id in &delta.killed {
+            let spawn = self.by_id[*id].mirror();
+            self.kill(&spawn);
+        }
+
+        for (id, gen, item) in &delta.spawned {
+            self.spawn_as(*id, *gen, item.clone());
+            self.record(Op::SpawnAs { id: *id, gen: *gen });
+            self.record_values(slice::from_ref(item));
+        }
+        if !delta.spawned.is_empty() { self.recycle_in_order(); }
+
+        for (id, patch) in &delta.changed {
+            let spawn = self.by_id[*id].mirror();
+            self.fetch(&spawn).apply(patch);
+        }
+        self.debug_check();
+        Ok(())
+    }
+}
+
+impl<ItemType: Diff, Properties> Swarm<ItemType, Properties> {
+
+    // a delta matches if killed and changed spawns are active, and spawned ones are not,
+    // every list holds a spawn id at most once
+    fn check_delta(&self, delta: &SwarmDelta<ItemType>) -> Result<(), DeltaError> {
+        let active = |id: SpawnId| self.by_id.get(id).map(Spawn::active);
+        let mut killed = vec![false; self.by_id.len()];
+        let mut spawned = vec![false; self.by_id.len()];
+        let mut changed = vec![false; self.by_id.len()];
+
+        for id in &delta.killed {
+            if active(*id) != Some(true) || mem::replace(&mut killed[*id], true) { 
+                return Err(DeltaError { id: *id }); 
+            }
+        }
+        for (id, _, _) in &delta.spawned {
+            let fits = match active(*id) {
+                Some(true) => killed[*id],
+                Some(false) => true,
+                None => false,
+            };
+            if !fits || mem::replace(&mut spawned[*id], true) { return Err(DeltaError { id: *id }); }
+        }
+        for (id, _) in &delta.changed {
+            if active(*id) != Some(true) || killed[*id] || mem::replace(&mut changed[*id], true) { 
+                return Err(DeltaError { id: *id }); 
+            }
+        }
+        let len = self.len.checked_sub(delta.killed.len()).and_then(|len| len.checked_add(delta.spawned.len()));
+        if !matches!(len, Some(len) if len <= self.max) {
+            return Err(DeltaError { id: delta.spawned.first().map_or(0, |s| s.0) });
+        }
+        Ok(())
+    }
+}
+
+impl<ItemType, Properties> Swarm<ItemType, Properties> {
+
+    // spawns the inactive spawn with the given id at the end of the spawned range
+    pub(crate) fn spawn_as(&mut self, id: SpawnId, gen: u32, item: ItemType) {
+        let mut pos = self.by_id[id].pos();
+        let constructed = self.pool.len();
+
+        // an unconstructed slot is swapped to the first unconstructed position first
+        if pos >= constructed {
+            self.swap_spawns(pos, constructed);
+            self.pool.push(item);
+            pos = constructed;
+        } else {
+            self.pool[pos] = item;
+        }
+        let target = self.len;
+        self.pool.swap(pos, target);
+        self.swap_spawns(pos, target);
+
+        let mut tag = self.by_id[id].0.borrow_mut();
+        tag.active = true;
+        tag.pending = false;
+        tag.ttl = None;
+        tag.born = self.ticks;
+        tag.gen = gen;
+        tag.changed = if self.tracking { Some(self.ticks) } else { None };
+        drop(tag);
+
+        self.len += 1;
+        self.counters.spawns += 1;
+        self.counters.peak_len = self.counters.peak_len.max(self.len);
+    }
+
+    // killed and skipped slots are recycled in position order after spawning by id
+    pub(crate) fn recycle_in_order(&mut self) {
+        self.free.clear();
+        self.free.extend(self.spawns[self.len..self.pool.len()].iter().rev().map(Spawn::mirror));
+    }
+
+    fn swap_spawns(&mut self, a: ObjectPosition, b: ObjectPosition) {
+        self.spawns.swap(a, b);
+        self.spawns[a].0.borrow_mut().pos = a;
+        self.spawns[b].0.borrow_mut().pos = b;
+    }
+}
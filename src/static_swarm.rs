@@ -0,0 +1,365 @@
+//! A fixed capacity Swarm that does not use the heap.
+//!
+//! StaticSwarm stores its pool objects in an inline array of `N` objects, and hands out
+//! StaticSpawns, which are plain index based handles, instead of reference counted Spawns.
+//! This makes it usable on `#![no_std]` targets that have no allocator, such as firmware.
+//! The StaticSwarm is still available with the `std` feature enabled.
+//!
+//! All `N` pool objects are constructed up front when the swarm is created, killed objects
+//! are recycled by later spawns, just like they are in Swarm.
+//!
+//! Example
+//! ```
+//! extern crate swarm_pool;
+//! use swarm_pool::static_swarm::StaticSwarm;
+//!
+//! #[derive(Default)]
+//! pub struct Led { brightness: u8 }
+//!
+//! let mut leds = StaticSwarm::<Led, (), 8>::new(());
+//! let red = leds.spawn().unwrap();
+//! let green = leds.spawn().unwrap();
+//!
+//! leds.for_each(|led| led.brightness = 100);
+//! leds.kill(&red);
+//!
+//! assert_eq!(leds.count(), 1);
+//! assert_eq!(leds.fetch_ref(&green).brightness, 100);
+//! assert!(!leds.is_active(&red));
+//! ```
+
+use super::types::*;
+
+/// A handle that points to a pool object in a StaticSwarm. StaticSpawns are Copy, a
+/// handle of a killed spawn is no longer active, even after its slot was spawned again.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StaticSpawn {
+    id: SpawnId,
+    gen: u32,
+}
+
+impl StaticSpawn {
+    /// Returns the identity of the slot this spawn points to. The identity is reused
+    /// when the slot is spawned again after a kill.
+    pub fn id(&self) -> SpawnId { self.id }
+}
+
+// the pool administration, seperated from the properties so that both can be
+// borrowed at the same time by StaticControl
+struct Slots<ItemType, const N: usize> {
+    items: [ItemType; N],
+    ids: [SpawnId; N],                  // per object position, the id of its spawn
+    positions: [ObjectPosition; N],     // per spawn id, the position of its object
+    gens: [u32; N],                     // per spawn id, its current generation
+    pending: [bool; N],                 // per spawn id, true if update() did not visit it yet
+    visit: [SpawnId; N],                // the spawn ids update() visits, in pool order
+    len: usize,
+}
+
+impl<ItemType, const N: usize> Slots<ItemType, N> {
+
+    fn is_active(&self, spawn: &StaticSpawn) -> bool {
+        spawn.id < N && self.gens[spawn.id] == spawn.gen && self.positions[spawn.id] < self.len
+    }
+
+    // the position of the object of an active spawn, handles of killed spawns would
+    // point to the wrong object
+    fn position(&self, spawn: &StaticSpawn) -> ObjectPosition {
+        assert!(self.is_active(spawn), "StaticSpawn {} was killed, its object cannot be fetched", spawn.id);
+        self.positions[spawn.id]
+    }
+
+    fn spawn_at(&self, pos: ObjectPosition) -> StaticSpawn {
+        let id = self.ids[pos];
+        StaticSpawn { id, gen: self.gens[id] }
+    }
+
+    // takes the next free slot, the id at the end of the active region is always free
+    fn activate(&mut self) -> Option<StaticSpawn> {
+        if self.len < N {
+            self.len += 1;
+            Some(self.spawn_at(self.len - 1))
+        } else {
+            None
+        }
+    }
+
+    fn kill(&mut self, target: &StaticSpawn) {
+        if !self.is_active(target) { return; }
+
+        let target_pos = self.positions[target.id];
+        let last_pos = self.len - 1;
+
+        // swap content and identities to back
+        self.items.swap(target_pos, last_pos);
+        self.ids.swap(target_pos, last_pos);
+        self.positions[self.ids[target_pos]] = target_pos;
+        self.positions[self.ids[last_pos]] = last_pos;
+
+        // outdate all handles to the killed spawn
+        self.gens[target.id] = self.gens[target.id].wrapping_add(1);
+        self.pending[target.id] = false;
+        self.len -= 1;
+    }
+
+    fn kill_all(&mut self) {
+        for pos in 0..self.len {
+            let id = self.ids[pos];
+            self.gens[id] = self.gens[id].wrapping_add(1);
+            self.pending[id] = false;
+        }
+        self.len = 0;
+    }
+}
+
+/// A Swarm object pool with a fixed capacity of `N` objects, that lives on the stack
+/// or in a static, see the module documentation.
+pub struct StaticSwarm<ItemType, Properties, const N: usize> {
+    slots: Slots<ItemType, N>,
+
+    pub properties: Properties,
+}
+
+impl<ItemType: Default, Properties, const N: usize> StaticSwarm<ItemType, Properties, N> {
+
+    /// Create a new StaticSwarm that can hold `N` spawns, all pool objects are
+    /// constructed using Default.
+    pub fn new(properties: Properties) -> Self {
+        StaticSwarm::new_with(properties, ItemType::default)
+    }
+}
+
+impl<ItemType, Properties, const N: usize> StaticSwarm<ItemType, Properties, N> {
+
+    /// Create a new StaticSwarm that can hold `N` spawns, all pool objects are
+    /// constructed by the `constructor`.
+    pub fn new_with(properties: Properties, constructor: ConstructHandler<ItemType>) -> Self {
+        StaticSwarm {
+            slots: Slots {
+                items: core::array::from_fn(|_| constructor()),
+                ids: core::array::from_fn(|i| i),
+                positions: core::array::from_fn(|i| i),
+                gens: [1; N],
+                pending: [false; N],
+                visit: [0; N],
+                len: 0,
+            },
+            properties,
+        }
+    }
+
+    /// Create a new pool instance, see Swarm.spawn().
+    /// Returns None if all `N` slots are spawned.
+    pub fn spawn(&mut self) -> Option<StaticSpawn> {
+        self.slots.activate()
+    }
+
+    /// Create a new pool instance with the given `value`, which replaces the object
+    /// stored in the slot. Returns None, and drops the value, if all slots are spawned.
+    pub fn spawn_with(&mut self, value: ItemType) -> Option<StaticSpawn> {
+        let spawn = self.slots.activate()?;
+        self.slots.items[self.slots.len - 1] = value;
+        Some(spawn)
+    }
+
+    /// Remove a spawn instance from the swarm pool update loops.
+    /// Spawns that are no longer active are ignored.
+    pub fn kill(&mut self, target: &StaticSpawn) {
+        self.slots.kill(target);
+    }
+
+    /// Remove all spawn instances
+    pub fn kill_all(&mut self) {
+        self.slots.kill_all();
+    }
+
+    /// Returns true if the spawn was not killed since it was spawned
+    pub fn is_active(&self, spawn: &StaticSpawn) -> bool {
+        self.slots.is_active(spawn)
+    }
+
+    /// Returns a spawn handle for the object at an object position
+    pub fn fetch_spawn(&self, pos: &ObjectPosition) -> StaticSpawn {
+        self.slots.spawn_at(*pos)
+    }
+
+    /// Returns a mutable reference to the pool object a spawn points to
+    ///
+    /// # Panics
+    /// If the spawn was killed, see `is_active()`.
+    pub fn fetch(&mut self, spawn: &StaticSpawn) -> &mut ItemType {
+        let pos = self.slots.position(spawn);
+        &mut self.slots.items[pos]
+    }
+
+    /// Returns a immutable reference to the pool object a spawn points to
+    ///
+    /// # Panics
+    /// If the spawn was killed, see `is_active()`.
+    pub fn fetch_ref(&self, spawn: &StaticSpawn) -> &ItemType {
+        &self.slots.items[self.slots.position(spawn)]
+    }
+
+    /// Returns a mutable reference to the pool object at an object position,
+    /// see Swarm.fetch_raw()
+    pub fn fetch_raw(&mut self, pos: &ObjectPosition) -> &mut ItemType {
+        &mut self.slots.items[*pos]
+    }
+
+    /// Returns the number of spawned instances
+    pub fn count(&self) -> usize { self.slots.len }
+
+    /// Returns the maximum number of instances that can be spawned, which is `N`
+    pub fn capacity(&self) -> usize { N }
+
+    /// Returns all spawned objects as one contiguous slice
+    pub fn active_slice(&self) -> &[ItemType] {
+        &self.slots.items[..self.slots.len]
+    }
+
+    /// Returns all spawned objects as one contiguous mutable slice
+    pub fn active_slice_mut(&mut self) -> &mut [ItemType] {
+        &mut self.slots.items[..self.slots.len]
+    }
+
+    /// Loops through all spawned instances, see Swarm.for_each()
+    pub fn for_each(&mut self, handler: ForEachHandler<ItemType>) {
+        for item in self.slots.items[..self.slots.len].iter_mut() {
+            handler(item);
+        }
+    }
+
+    /// Loops through all spawned instances by their object position, see Swarm.for_all()
+    pub fn for_all(&mut self, handler: ForAllHandler<ItemType, Properties>) {
+        let len = self.slots.len;
+        let mut i = 0;
+
+        while i < len {
+            handler(&i, &mut self.slots.items[..len], &mut self.properties);
+            i += 1;
+        }
+    }
+
+    /// Loops through all spawned instances and returns a `StaticControl` object via a
+    /// callback handler, see Swarm.update().
+    ///
+    /// Every spawn that was active when the loop started is visited once, unless it
+    /// is killed before its turn. Spawns created during the loop are visited the next
+    /// time update() is called.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::static_swarm::StaticSwarm;
+    ///
+    /// let mut swarm = StaticSwarm::<u8, usize, 8>::new(0);
+    /// for value in 0..4 { swarm.spawn_with(value); }
+    ///
+    /// swarm.update(|ctl| {
+    ///     *ctl.properties += 1;
+    ///     if *ctl.target() % 2 == 0 { ctl.kill_current(); }
+    /// });
+    ///
+    /// assert_eq!(swarm.properties, 4);
+    /// assert_eq!(swarm.count(), 2);
+    /// ```
+    pub fn update(&mut self, handler: StaticUpdateHandler<ItemType, Properties, N>) {
+        let len = self.slots.len;
+        for pos in 0..len {
+            let id = self.slots.ids[pos];
+            self.slots.visit[pos] = id;
+            self.slots.pending[id] = true;
+        }
+
+        let mut ctl = StaticControl {
+            slots: &mut self.slots,
+            pos: 0,
+            properties: &mut self.properties,
+        };
+
+        for i in 0..len {
+            let id = ctl.slots.visit[i];
+            if ctl.slots.pending[id] {
+                ctl.slots.pending[id] = false;
+                ctl.pos = ctl.slots.positions[id];
+                handler(&mut ctl);
+            }
+        }
+    }
+}
+
+/// StaticControl is passed to the StaticUpdateHandler during the StaticSwarm.update()
+/// loop, it does for StaticSwarm what SwarmControl does for Swarm.
+pub struct StaticControl<'a, ItemType, Properties, const N: usize> {
+    slots: &'a mut Slots<ItemType, N>,
+    pos: ObjectPosition, // the pool index of the currently updating spawn
+
+    pub properties: &'a mut Properties,
+}
+
+impl<'a, ItemType, Properties, const N: usize> StaticControl<'a, ItemType, Properties, N> {
+
+    /// Returns a mutable reference to the pool object that is currently being updated
+    pub fn target(&mut self) -> &mut ItemType {
+        &mut self.slots.items[self.pos]
+    }
+
+    /// Returns the spawn of the pool object that is currently being updated
+    pub fn target_spawn(&self) -> StaticSpawn {
+        self.slots.spawn_at(self.pos)
+    }
+
+    /// Returns the object position of the pool object that is currently being updated
+    pub fn head(&self) -> ObjectPosition { self.pos }
+
+    /// Returns a mutable reference to the pool object a spawn points to
+    ///
+    /// # Panics
+    /// If the spawn was killed, see `is_active()`.
+    pub fn fetch(&mut self, spawn: &StaticSpawn) -> &mut ItemType {
+        let pos = self.slots.position(spawn);
+        &mut self.slots.items[pos]
+    }
+
+    /// Returns a immutable reference to the pool object a spawn points to
+    ///
+    /// # Panics
+    /// If the spawn was killed, see `is_active()`.
+    pub fn fetch_ref(&self, spawn: &StaticSpawn) -> &ItemType {
+        &self.slots.items[self.slots.position(spawn)]
+    }
+
+    /// Returns true if the spawn was not killed since it was spawned
+    pub fn is_active(&self, spawn: &StaticSpawn) -> bool {
+        self.slots.is_active(spawn)
+    }
+
+    /// Returns the number of spawned instances
+    pub fn count(&self) -> usize { self.slots.len }
+
+    /// Returns the maximum number of instances that can be spawned, which is `N`
+    pub fn capacity(&self) -> usize { N }
+
+    /// Create a new pool instance, it is visited the next time update() is called
+    pub fn spawn(&mut self) -> Option<StaticSpawn> {
+        self.slots.activate()
+    }
+
+    /// Create a new pool instance with the given `value`, see StaticSwarm.spawn_with()
+    pub fn spawn_with(&mut self, value: ItemType) -> Option<StaticSpawn> {
+        let spawn = self.slots.activate()?;
+        self.slots.items[self.slots.len - 1] = value;
+        Some(spawn)
+    }
+
+    /// Remove a spawn instance, spawns that were not visited yet are skipped
+    pub fn kill(&mut self, target: &StaticSpawn) {
+        self.slots.kill(target);
+    }
+
+    /// Remove the currently updating spawn instance
+    pub fn kill_current(&mut self) {
+        let target = self.target_spawn();
+        self.slots.kill(&target);
+    }
+}
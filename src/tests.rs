@@ -1,9 +1,15 @@
 //! Swarm unit tests
 
-#[cfg(test)]
 use crate::*;
-use crate::{ Spawn };
-//use crate::tools::byte_str::ByteStr;
+use crate::static_swarm::StaticSwarm;
+use crate::record::{ Op, OpLog };
+use crate::replication::{ Diff, SwarmDelta };
+use crate::{ Spawn, Swarm, SwarmControl, WeakSpawn };
+use std::collections::{ BTreeMap, HashSet };
+use std::convert::TryInto;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use crate::tools::{ byte_str::ByteStr, sized_pool::{ self, SizedPool16, SizedPool64 } };
+use crate::tools::codec::CodecError;
 
 // sest values
 
@@ -54,6 +60,7 @@ fn referencing_spawn_instance_bodies() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn using_spawn_reference_info() {
     let mut swarm = Swarm::<Minion, _>::new(10, ());
     let spawn1 = swarm.spawn().unwrap();
@@ -64,6 +71,7 @@ fn using_spawn_reference_info() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn spawn_info_can_be_shared() {
     let mut swarm = Swarm::<Minion, _>::new(10, ());
     let spawn1 = swarm.spawn().unwrap();
@@ -171,11 +179,38 @@ fn forall_cross_referencing() {
     assert_eq!(swarm.fetch_ref(&s_cristy).value, 2);
 }
 
+#[test]
+fn slices_only_hold_spawned_instances() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(10, SwarmData { counter: 0 });
+    let spawns: Vec<Spawn> = (0..5).map(|_| swarm.spawn().unwrap()).collect();
+    swarm.kill(&spawns[1]);
+    swarm.kill(&spawns[3]);
+
+    assert_eq!(swarm.active_slice().len(), 3);
+    swarm.active_slice_mut().iter_mut().for_each(|m| m.value = 1);
+
+    swarm.for_all(|index, list, props| {
+        props.counter = list.len();
+        list[*index].add_one();
+    });
+    assert_eq!(swarm.properties.counter, 3);
+
+    swarm.for_each_chunk(2, |chunk| {
+        assert!(chunk.len() <= 2);
+        chunk.iter_mut().for_each(|m| m.add_one());
+    });
+
+    // killed spawns were never touched
+    assert!(swarm.active_slice().iter().all(|m| m.value == 3));
+    assert_eq!(swarm.fetch_ref(&spawns[1]).value, 0);
+    assert_eq!(swarm.fetch_ref(&spawns[3]).value, 0);
+}
+
 
 // swarm control tests
 
 #[test]
-#[allow(unused_must_use)]
+#[allow(unused_must_use, clippy::bool_assert_comparison)]
 fn killing_spawned_instances() {
     let mut swarm = Swarm::<Minion, _>::new(10, ());
     let spawn1 = swarm.spawn().unwrap();
@@ -213,6 +248,7 @@ fn killing_spawned_instances() {
  }
 
  #[test]
+ #[allow(clippy::bool_assert_comparison)]
  fn kill_all_spawned_instances() {
     let mut swarm = Swarm::<Minion, _>::new(10, ());
     
@@ -326,6 +362,7 @@ fn creating_spawns_during_update_loop() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn killing_spawns_during_update_loop() {
     let mut swarm = Swarm::<Minion, _>::new(10, ());
     
@@ -401,6 +438,20 @@ fn killing_spawns_during_update_loop() {
     assert_eq!(spawn3.active(), false);
 }
 
+#[test]
+fn control_for_while_stops_at_the_first_rejected_object() {
+    let mut swarm = Swarm::<u8, _>::new(10, ());
+    swarm.populate(&[1, 2, 3, 4]);
+
+    swarm.update(|ctl| {
+        if ctl.head() != 0 { return; }
+
+        let stopped = ctl.for_while(|value| *value < 3).unwrap();
+        assert_eq!(*ctl.fetch_ref(&stopped), 3);
+        assert!(ctl.for_while(|value| *value < 10).is_none());
+    });
+}
+
 #[derive(Default, Clone, Debug, PartialEq)] struct Image(bool);
 #[derive(Default, Clone, Debug, PartialEq)] struct Position(f32, f32);
 #[derive(Default, Clone, Debug, PartialEq)] struct Speed(f32);
@@ -416,6 +467,7 @@ pub struct Entity {
 
 
 #[test]
+#[allow(non_snake_case, unused_variables)]
 fn using_swarm_for_ECS() {
     let mut swarm = Swarm::<Entity, _>::new(10, ());
     
@@ -524,4 +576,1541 @@ fn spawn_specific_type_by_factory_definition() {
     assert_eq!(swarm.fetch_ref(&soldier_2).name, "soldier");
     assert_eq!(swarm.fetch_ref(&truck_1).name, "truck");
     assert_eq!(swarm.fetch_ref(&truck_2).name, "truck");
-}
\ No newline at end of file
+}
+
+#[test]
+fn spawn_type_does_not_spawn_unknown_types() {
+    let names = ("soldier", "truck");
+    let mut swarm = Swarm::<Minion, UnitNames>::new(10, names);
+    swarm.add_factory(0, soldier_factory);
+
+    assert!(swarm.spawn_type(1).is_none());
+    assert_eq!(swarm.count(), 0);
+
+    let soldier = swarm.spawn_type(0).unwrap();
+    assert_eq!(swarm.fetch_ref(&soldier).name, "soldier");
+    assert_eq!(swarm.count(), 1);
+}
+// group tests
+
+const ENEMIES: usize = 0;
+const PROJECTILES: usize = 1;
+
+#[test]
+fn iterating_over_group_members() {
+    let mut swarm = Swarm::<Minion, _>::new(10, ());
+    let player = swarm.spawn().unwrap();
+    let orc = swarm.spawn().unwrap();
+    let bullet = swarm.spawn().unwrap();
+
+    swarm.add_to_group(&orc, ENEMIES);
+    swarm.add_to_group(&bullet, PROJECTILES);
+    swarm.add_to_group(&orc, ENEMIES); // adding twice is ignored
+
+    swarm.for_each_in_group(ENEMIES, |obj| obj.value += 1);
+    swarm.for_each_in_group(PROJECTILES, |obj| obj.value += 2);
+
+    assert_eq!(swarm.fetch_ref(&player).value, 0);
+    assert_eq!(swarm.fetch_ref(&orc).value, 1);
+    assert_eq!(swarm.fetch_ref(&bullet).value, 2);
+    assert_eq!(swarm.iter_group(ENEMIES).count(), 1);
+    assert!(swarm.in_group(&orc, ENEMIES));
+    assert!(!swarm.in_group(&player, ENEMIES));
+
+    swarm.remove_from_group(&orc, ENEMIES);
+    swarm.remove_from_group(&orc, ENEMIES); // removing twice is ignored
+    assert!(!swarm.in_group(&orc, ENEMIES));
+    assert_eq!(swarm.iter_group(ENEMIES).count(), 0);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+}
+
+#[test]
+fn killed_spawns_leave_their_groups() {
+    let mut swarm = Swarm::<Minion, _>::new(10, ());
+    let orc = swarm.spawn().unwrap();
+    let goblin = swarm.spawn().unwrap();
+    let troll = swarm.spawn().unwrap();
+
+    swarm.add_to_group(&orc, ENEMIES);
+    swarm.add_to_group(&orc, PROJECTILES);
+    swarm.add_to_group(&troll, ENEMIES);
+
+    // killing the orc moves the troll into position 0
+    swarm.kill(&orc);
+    assert_eq!(troll.pos(), 0);
+    assert!(!swarm.in_group(&orc, ENEMIES));
+    assert_eq!(swarm.iter_group(PROJECTILES).count(), 0);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+
+    swarm.for_each_in_group(ENEMIES, |obj| obj.value = 42);
+    assert_eq!(swarm.fetch_ref(&troll).value, 42);
+    assert_eq!(swarm.fetch_ref(&goblin).value, 0);
+
+    // re-using the killed slot does not bring back its old memberships
+    let spider = swarm.spawn().unwrap();
+    assert_eq!(spider, orc);
+    assert!(!swarm.in_group(&spider, ENEMIES));
+
+    swarm.update(|ctl| {
+        if ctl.in_group(&ctl.target_spawn(), ENEMIES) { ctl.kill_current(); }
+    });
+    assert_eq!(swarm.iter_group(ENEMIES).count(), 0);
+    assert_eq!(swarm.count(), 2);
+
+    swarm.add_to_group(&goblin, ENEMIES);
+    swarm.kill_all();
+    assert_eq!(swarm.iter_group(ENEMIES).count(), 0);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+}
+
+// spatial grid tests
+
+#[test]
+fn spatial_grid_agrees_with_brute_force() {
+    use crate::tools::spatial::SpatialGrid;
+
+    let mut swarm = Swarm::<(f32, f32), _>::new(200, ());
+    let mut seed: u32 = 7;
+    for _ in 0..150 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let x = (seed >> 8) as f32 % 120.0 - 10.0; // some objects are outside of the grid
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let y = (seed >> 8) as f32 % 100.0;
+        let spawn = swarm.spawn().unwrap();
+        *swarm.fetch(&spawn) = (x, y);
+    }
+    // kill a few so that positions have moved around before building the grid
+    for pos in [3, 40, 77, 120].iter() {
+        let spawn = swarm.fetch_spawn(pos);
+        swarm.kill(&spawn);
+    }
+
+    let mut grid = SpatialGrid::new((0.0, 0.0), (100.0, 100.0), 8.0, |p: &(f32, f32)| *p);
+    grid.rebuild(&swarm);
+    assert_eq!(grid.count(), swarm.count());
+
+    let distance = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+    let all: Vec<Spawn> = (0..swarm.count()).map(|pos| swarm.fetch_spawn(&pos)).collect();
+
+    for center in [(50.0, 50.0), (0.0, 0.0), (-20.0, 90.0), (99.0, 3.0)].iter() {
+        let center = *center;
+
+        let mut found = grid.query_radius(&swarm, center, 17.0);
+        let mut expected: Vec<Spawn> = all.iter()
+            .filter(|s| distance(*swarm.fetch_ref(s), center) <= 17.0 * 17.0)
+            .cloned().collect();
+        found.sort_by_key(|s| s.id());
+        expected.sort_by_key(|s| s.id());
+        assert_eq!(found, expected);
+
+        let mut found = grid.query_aabb(&swarm, (center.0 - 5.0, center.1 - 20.0), (center.0 + 25.0, center.1));
+        let mut expected: Vec<Spawn> = all.iter()
+            .filter(|s| {
+                let p = *swarm.fetch_ref(s);
+                p.0 >= center.0 - 5.0 && p.0 <= center.0 + 25.0 && p.1 >= center.1 - 20.0 && p.1 <= center.1
+            })
+            .cloned().collect();
+        found.sort_by_key(|s| s.id());
+        expected.sort_by_key(|s| s.id());
+        assert_eq!(found, expected);
+
+        let nearest = grid.nearest(&swarm, center, 5);
+        let mut by_distance: Vec<f32> = all.iter().map(|s| distance(*swarm.fetch_ref(s), center)).collect();
+        by_distance.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let nearest: Vec<f32> = nearest.iter().map(|s| distance(*swarm.fetch_ref(s), center)).collect();
+        assert_eq!(nearest, by_distance[..5].to_vec());
+    }
+}
+
+// lifetime tests
+
+fn count_expired(m: &mut Minion, expired: &mut SwarmData) {
+    expired.counter += m.value;
+}
+
+#[test]
+fn spawns_expire_after_their_lifetime() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(10, SwarmData { counter: 0 });
+    swarm.on_expire(count_expired);
+
+    let forever = swarm.spawn().unwrap();
+    let short = swarm.spawn_with_ttl(1).unwrap();
+    let long = swarm.spawn_with_ttl(3).unwrap();
+    let medium = swarm.spawn().unwrap();
+    swarm.set_ttl(&medium, Some(2));
+
+    swarm.fetch(&short).value = 1;
+    swarm.fetch(&medium).value = 10;
+    swarm.fetch(&long).value = 100;
+
+    swarm.tick();
+    assert_eq!(swarm.ticks(), 1);
+    assert_eq!(swarm.count(), 3);
+    assert_eq!(swarm.properties.counter, 1);
+    assert!(!short.active());
+    assert_eq!(medium.ttl(), Some(1));
+    assert_eq!(long.ttl(), Some(2));
+
+    swarm.tick();
+    assert_eq!(swarm.count(), 2);
+    assert_eq!(swarm.properties.counter, 11);
+    assert!(!medium.active());
+
+    swarm.tick();
+    assert_eq!(swarm.count(), 1);
+    assert_eq!(swarm.properties.counter, 111);
+    assert!(forever.active());
+    assert_eq!(forever.ttl(), None);
+    assert_eq!(forever.pos(), 0);
+
+    // recycled spawns do not inherit the lifetime of their previous life
+    let recycled = swarm.spawn().unwrap();
+    assert_eq!(recycled.ttl(), None);
+}
+
+#[test]
+fn expiring_all_spawns_in_one_tick() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(10, SwarmData { counter: 0 });
+    swarm.on_expire(count_expired);
+
+    for _ in 0..10 { 
+        let spawn = swarm.spawn_with_ttl(1).unwrap(); 
+        swarm.fetch(&spawn).value = 1;
+    }
+    swarm.tick();
+
+    assert_eq!(swarm.count(), 0);
+    assert_eq!(swarm.properties.counter, 10);
+}
+
+#[test]
+fn update_visits_every_spawn_once_after_kill() {
+    let mut swarm = Swarm::<Minion, _>::new(10, ());
+    let spawn1 = swarm.spawn().unwrap();
+    let _spawn2 = swarm.spawn().unwrap();
+    let _spawn3 = swarm.spawn().unwrap();
+
+    swarm.kill(&spawn1);
+    swarm.spawn();
+    swarm.update(|ctl| ctl.target().value += 1);
+
+    swarm.for_each(|obj| assert_eq!(obj.value, 1));
+}
+
+// world tests
+
+#[derive(Default, Clone)]
+pub struct Arrow {
+    damage: usize,
+    target: crate::world::TypedSpawn<Minion, SwarmData>,
+}
+
+#[test]
+fn world_holds_swarms_by_type() {
+    use crate::world::World;
+
+    let mut world = World::new();
+    assert!(world.insert(Swarm::<Minion, _>::new(10, SwarmData { counter: 0 })).is_none());
+    assert!(world.insert(Swarm::<Arrow, _>::new(5, ())).is_none());
+    assert_eq!(world.count(), 2);
+    assert!(world.contains::<Minion, SwarmData>());
+    assert!(!world.contains::<Entity, ()>());
+
+    assert_eq!(world.get::<Minion, SwarmData>().unwrap().capacity(), 10);
+    assert_eq!(world.get::<Arrow, ()>().unwrap().capacity(), 5);
+    assert!(world.get::<Minion, ()>().is_none(), "properties type should match as well");
+    assert!(world.remove::<Minion, ()>().is_none());
+    assert_eq!(world.count(), 2);
+
+    // swarms with other properties do not replace each other
+    assert!(world.insert(Swarm::<Minion, _>::new(3, ())).is_none());
+    assert_eq!(world.get::<Minion, SwarmData>().unwrap().capacity(), 10);
+    assert_eq!(world.remove::<Minion, ()>().unwrap().capacity(), 3);
+
+    let old = world.insert(Swarm::<Arrow, _>::new(8, ())).unwrap();
+    assert_eq!(old.capacity(), 5);
+    assert_eq!(world.get::<Arrow, ()>().unwrap().capacity(), 8);
+
+    let swarm = world.remove::<Arrow, ()>().unwrap();
+    assert_eq!(swarm.capacity(), 8);
+    assert!(!world.contains::<Arrow, ()>());
+    assert!(world.spawn::<Arrow, ()>().is_none());
+}
+
+#[test]
+fn world_systems_borrow_two_swarms() {
+    use crate::world::World;
+
+    let mut world = World::new();
+    world.insert(Swarm::<Minion, _>::new(10, SwarmData { counter: 0 }));
+    world.insert(Swarm::<Arrow, _>::new(10, ()));
+
+    let orc = world.spawn::<Minion, SwarmData>().unwrap();
+    let goblin = world.spawn::<Minion, SwarmData>().unwrap();
+    world.fetch(&orc).unwrap().value = 5;
+    world.fetch(&goblin).unwrap().value = 2;
+
+    for target in [&orc, &goblin, &goblin].iter() {
+        let arrow = world.spawn::<Arrow, ()>().unwrap();
+        *world.fetch(&arrow).unwrap() = Arrow { damage: 2, target: (*target).mirror() };
+    }
+
+    // arrows hit their targets and minions without health are killed
+    let survivors = world.update(|arrows: &mut Swarm<Arrow, ()>, minions: &mut Swarm<Minion, SwarmData>| {
+        for pos in 0..arrows.count() {
+            let arrow = arrows.fetch_raw(&pos).clone();
+            let target = minions.fetch(arrow.target.spawn());
+            target.value = target.value.saturating_sub(arrow.damage);
+        }
+        arrows.kill_all();
+        minions.update(|ctl| if ctl.target().value == 0 { ctl.kill_current(); });
+        minions.count()
+    });
+    assert_eq!(survivors, Some(1));
+
+    assert_eq!(world.get::<Arrow, ()>().unwrap().count(), 0);
+    assert_eq!(world.get::<Minion, SwarmData>().unwrap().count(), 1);
+    assert_eq!(world.fetch_ref(&orc).unwrap().value, 3);
+    assert!(orc.spawn().active());
+    assert!(!goblin.spawn().active());
+}
+
+#[test]
+fn world_systems_leave_the_world_alone_when_a_swarm_is_missing() {
+    use crate::world::World;
+
+    let mut world = World::new();
+    world.insert(Swarm::<Arrow, _>::new(10, ()));
+    world.spawn::<Arrow, ()>().unwrap();
+
+    let result = world.update(|_arrows: &mut Swarm<Arrow, ()>, _minions: &mut Swarm<Minion, ()>| {});
+    assert_eq!(result, None);
+    assert_eq!(world.get::<Arrow, ()>().unwrap().count(), 1);
+    let result = world.update(|_minions: &mut Swarm<Minion, ()>, _arrows: &mut Swarm<Arrow, ()>| {});
+    assert_eq!(result, None);
+    assert_eq!(world.count(), 1);
+}
+
+#[test]
+#[should_panic]
+fn world_systems_cannot_borrow_a_swarm_twice() {
+    use crate::world::World;
+
+    let mut world = World::new();
+    world.insert(Swarm::<Minion, _>::new(10, ()));
+    world.update(|_a: &mut Swarm<Minion, ()>, _b: &mut Swarm<Minion, ()>| {});
+}
+
+// double buffered tests
+
+fn sum_of_previous(_pos: &usize, prev: &[Minion], next: &mut Minion, _props: &mut ()) {
+    next.value = prev.iter().map(|m| m.value).sum();
+}
+
+#[test]
+fn buffered_loop_does_not_depend_on_spawn_order() {
+    // swarm a spawns 1, 2, 3 in order
+    let mut a = Swarm::<Minion, _>::new(10, ());
+    let a1 = a.spawn().unwrap();
+    let a2 = a.spawn().unwrap();
+    let a3 = a.spawn().unwrap();
+    a.fetch(&a1).value = 1;
+    a.fetch(&a2).value = 2;
+    a.fetch(&a3).value = 3;
+
+    // swarm b holds the same values, but in a different order because of a kill
+    let mut b = Swarm::<Minion, _>::new(10, ());
+    let b0 = b.spawn().unwrap();
+    let b1 = b.spawn().unwrap();
+    let b2 = b.spawn().unwrap();
+    let b3 = b.spawn().unwrap();
+    b.kill(&b0);
+    b.fetch(&b1).value = 1;
+    b.fetch(&b2).value = 2;
+    b.fetch(&b3).value = 3;
+    assert_eq!(b3.pos(), 0);
+
+    a.for_all_buffered(sum_of_previous);
+    b.for_all_buffered(sum_of_previous);
+
+    for (sa, sb) in [(&a1, &b1), (&a2, &b2), (&a3, &b3)].iter() {
+        assert_eq!(a.fetch_ref(sa).value, 6);
+        assert_eq!(b.fetch_ref(sb).value, 6);
+    }
+
+    // buffers are swapped, the next loop reads the values written by the last one
+    a.for_all_buffered(sum_of_previous);
+    assert_eq!(a.fetch_ref(&a1).value, 18);
+
+    // objects keep their other fields, and spawning after a buffered loop still works
+    a.fetch(&a2).name = "two";
+    a.for_all_buffered(sum_of_previous);
+    assert_eq!(a.fetch_ref(&a2).name, "two");
+    let a4 = a.spawn().unwrap();
+    a.fetch(&a4).value = 1;
+    a.for_all_buffered(sum_of_previous);
+    assert_eq!(a.fetch_ref(&a4).value, 54 * 3 + 1);
+}
+
+#[test]
+fn recycled_slots_keep_their_last_object_after_buffered_loops() {
+    let mut swarm = Swarm::<Minion, _>::new(10, ());
+    let first = swarm.spawn().unwrap();
+    let second = swarm.spawn().unwrap();
+    swarm.for_all_buffered(|pos, prev, next, _props| next.value = prev[*pos].value + 1);
+
+    // the killed object stays in its slot, like it does without buffered loops
+    swarm.fetch(&second).value = 42;
+    swarm.kill(&second);
+    swarm.for_all_buffered(|pos, prev, next, _props| next.value = prev[*pos].value + 1);
+    assert_eq!(swarm.fetch_ref(&first).value, 2);
+
+    let recycled = swarm.spawn().unwrap();
+    assert_eq!(swarm.fetch_ref(&recycled).value, 42);
+}
+
+// every minion takes the previous value of the next minion in the ring
+fn rotate_ring(ctl: &mut SwarmControl<Minion, Vec<Spawn>>) {
+    let at = ctl.properties.iter().position(|s| s.id() == ctl.target_spawn().id()).unwrap();
+    let next = ctl.properties[(at + 1) % ctl.properties.len()].mirror();
+    ctl.target().value = ctl.previous(&next).unwrap().value;
+}
+
+#[test]
+fn buffered_update_does_not_depend_on_spawn_order() {
+    // swarm a spawns 1, 2, 3 in order, swarm b holds them in a different order
+    let mut a = Swarm::<Minion, Vec<Spawn>>::new(10, Vec::new());
+    let mut b = Swarm::<Minion, Vec<Spawn>>::new(10, Vec::new());
+    b.spawn().unwrap();
+    for value in 1..4 {
+        a.spawn_with(Minion { name: "", value }).unwrap();
+        b.spawn_with(Minion { name: "", value }).unwrap();
+    }
+    b.kill(&b.fetch_spawn(&0));
+    a.properties = (0..3).map(|pos| a.fetch_spawn(&pos)).collect();
+    b.properties = vec![b.fetch_spawn(&1), b.fetch_spawn(&2), b.fetch_spawn(&0)];
+
+    a.update_buffered(rotate_ring);
+    b.update_buffered(rotate_ring);
+    for swarm in [&a, &b].iter() {
+        let values: Vec<usize> = swarm.properties.iter().map(|s| swarm.fetch_ref(s).value).collect();
+        assert_eq!(values, vec![2, 3, 1]);
+    }
+
+    // objects killed during the loop can still be read, respawned ones cannot
+    let last = a.properties[2].mirror();
+    a.update_buffered(|ctl| {
+        let last = ctl.properties[2].mirror();
+        if ctl.target().value == 2 {
+            ctl.kill(&last);
+        }
+        assert_eq!(ctl.previous(&last).unwrap().value, 1);
+    });
+    assert!(!last.active());
+    a.update_buffered(|ctl| {
+        let last = ctl.properties[2].mirror();
+        if !last.active() { ctl.spawn(); }
+        assert!(ctl.previous(&last).is_none());
+    });
+
+    // the unbuffered loop has no previous versions
+    a.update(|ctl| {
+        let spawn = ctl.target_spawn();
+        assert!(ctl.previous(&spawn).is_none());
+    });
+    assert!(a.check_integrity().is_ok());
+}
+
+// lazy construction tests
+
+static CONSTRUCTED: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Implements neither Default nor Clone, and counts its constructions and drops
+pub struct Resource {
+    data: Vec<usize>,
+}
+
+impl Drop for Resource {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn new_resource() -> Resource {
+    CONSTRUCTED.fetch_add(1, Ordering::SeqCst);
+    Resource { data: vec![1, 2, 3] }
+}
+
+#[test]
+fn slots_are_constructed_lazily_and_dropped_with_the_swarm() {
+    let constructed = || CONSTRUCTED.load(Ordering::SeqCst);
+    let dropped = || DROPPED.load(Ordering::SeqCst);
+
+    let mut swarm = Swarm::<Resource, _>::new_with(100, (), new_resource);
+    assert_eq!(constructed(), 0);
+
+    let a = swarm.spawn().unwrap();
+    let b = swarm.spawn().unwrap();
+    assert_eq!(constructed(), 2);
+
+    // killed slots are recycled, not constructed again
+    swarm.kill(&a);
+    let c = swarm.spawn().unwrap();
+    swarm.fetch(&c).data.push(4);
+    assert_eq!(constructed(), 2);
+    assert_eq!(dropped(), 0);
+
+    // spawning with a value drops the object of the recycled slot
+    swarm.kill(&b);
+    let d = swarm.spawn_with(Resource { data: Vec::new() }).unwrap();
+    assert_eq!(constructed(), 2);
+    assert_eq!(dropped(), 1);
+    assert!(swarm.fetch_ref(&d).data.is_empty());
+    assert_eq!(swarm.fetch_ref(&c).data, vec![1, 2, 3, 4]);
+
+    drop(swarm);
+    assert_eq!(dropped(), 3);
+}
+
+#[test]
+fn killing_moves_objects_without_copying_them() {
+    let mut swarm = Swarm::<Vec<usize>, _>::new_with(10, (), || vec![7; 32]);
+    let a = swarm.spawn().unwrap();
+    let b = swarm.spawn().unwrap();
+    let c = swarm.spawn().unwrap();
+    let heap_a = swarm.fetch_ref(&a).as_ptr();
+    let heap_c = swarm.fetch_ref(&c).as_ptr();
+
+    // c is moved into the position of a, both keep their heap allocations
+    swarm.kill(&a);
+    assert_eq!(c.pos(), 0);
+    assert_eq!(b.pos(), 1);
+    assert_eq!(swarm.fetch_ref(&c).as_ptr(), heap_c);
+    assert_eq!(swarm.fetch_ref(&a).as_ptr(), heap_a);
+    assert_eq!(swarm.fetch_spawn(&0), c);
+    assert_eq!(swarm.fetch_spawn(&2), a);
+}
+
+
+// statistics tests
+
+#[test]
+fn stats_track_spawns_kills_and_lifetimes() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(4, SwarmData { counter: 0 });
+    swarm.spawn_with_ttl(1);
+    swarm.spawn_with_ttl(3);
+    let keeper = swarm.spawn().unwrap();
+
+    // kills during update and by expiring are counted as well
+    swarm.update(|ctl| {
+        ctl.spawn();
+        ctl.spawn();
+    });
+    swarm.tick();
+    swarm.tick();
+    swarm.tick();
+
+    let stats = swarm.stats();
+    assert_eq!(stats.spawns, 4);
+    assert_eq!(stats.failed_spawns, 5);
+    assert_eq!(stats.kills, 2);
+    assert_eq!(stats.len, 2);
+    assert_eq!(stats.peak_len, 4);
+    assert_eq!(stats.free_len, 2);
+    assert_eq!(stats.average_lifetime, 2.0);
+
+    swarm.reset_stats();
+    swarm.kill(&keeper);
+    let stats = swarm.stats();
+    assert_eq!(stats.spawns, 0);
+    assert_eq!(stats.kills, 1);
+    assert_eq!(stats.peak_len, 2);
+    assert_eq!(stats.average_lifetime, 3.0);
+}
+
+
+// scoped spawn tests
+
+#[test]
+fn dropped_guards_kill_their_spawn_once() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(4, SwarmData { counter: 0 });
+    let ghost = swarm.spawn_scoped().unwrap();
+    let kept = swarm.spawn_scoped().unwrap().into_inner();
+    let stale = swarm.spawn_scoped().unwrap();
+    swarm.fetch(&ghost).value = 1;
+
+    // the kill is queued until the swarm is used again
+    drop(ghost);
+    assert_eq!(swarm.count(), 3);
+    swarm.update(|ctl| ctl.properties.counter += 1);
+    assert_eq!(swarm.properties.counter, 2);
+    assert_eq!(swarm.count(), 2);
+
+    // a guard of a killed spawn does not kill the object that reuses its slot
+    swarm.kill(&stale);
+    let reused = swarm.spawn().unwrap();
+    assert_eq!(reused.id(), stale.id());
+    drop(stale);
+    swarm.kill_dropped();
+
+    assert!(kept.active());
+    assert!(reused.active());
+    assert_eq!(swarm.count(), 2);
+}
+
+
+// weak spawn tests
+
+#[test]
+fn weak_spawns_only_upgrade_while_their_object_lives() {
+    let mut swarm = Swarm::<Minion, Option<WeakSpawn>>::new(4, None);
+    let other = Swarm::<Minion, ()>::new(4, ());
+    let hunter = swarm.spawn().unwrap();
+    let prey = swarm.spawn().unwrap();
+    swarm.properties = Some(prey.downgrade());
+    assert_eq!(prey.downgrade().upgrade(&swarm), Some(prey.mirror()));
+    assert!(prey.downgrade().upgrade(&other).is_none());
+
+    // the hunter kills its prey during update, the weak target is gone afterwards
+    swarm.fetch(&hunter).value = 1;
+    swarm.update(|ctl| {
+        let target = ctl.properties.as_ref().and_then(|weak| ctl.upgrade(weak));
+        if let (1, Some(prey)) = (ctl.target().value, target) {
+            ctl.kill(&prey);
+        }
+    });
+    assert_eq!(swarm.count(), 1);
+    let reused = swarm.spawn().unwrap();
+    assert_eq!(reused.id(), prey.id());
+    assert!(swarm.properties.as_ref().unwrap().upgrade(&swarm).is_none());
+
+    // kill_all outdates weak spawns as well
+    let weak_hunter = hunter.downgrade();
+    swarm.kill_all();
+    swarm.spawn();
+    assert!(weak_hunter.upgrade(&swarm).is_none());
+}
+
+#[test]
+fn weak_spawns_of_killed_spawns_never_upgrade() {
+    let mut swarm = Swarm::<Minion, ()>::new(1, ());
+    let orc = swarm.spawn().unwrap();
+    swarm.kill(&orc);
+    let target = orc.downgrade();
+
+    // the goblin reuses the slot of the orc, the dead orc's target must not reach it
+    let goblin = swarm.spawn().unwrap();
+    assert_eq!(goblin.id(), orc.id());
+    assert!(target.upgrade(&swarm).is_none());
+}
+
+// spawn identity tests
+
+#[test]
+#[allow(clippy::mutable_key_type)] // spawns hash by their id, which is never mutated
+fn spawns_work_as_map_keys_and_survive_encoding() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(8, SwarmData { counter: 0 });
+    let spawns: Vec<Spawn> = (0..4).map(|_| swarm.spawn().unwrap()).collect();
+
+    let mut selection = HashSet::new();
+    selection.insert(spawns[2].mirror());
+    selection.insert(spawns[2].clone());
+    selection.insert(spawns[0].mirror());
+    assert_eq!(selection.len(), 2);
+    assert!(selection.contains(&spawns[0]));
+
+    // killing moves spawns around, but their order and hash stay the same
+    let targets: BTreeMap<Spawn, usize> = spawns.iter().rev().map(|s| (s.mirror(), s.id())).collect();
+    let bits: Vec<u64> = spawns.iter().map(Spawn::to_bits).collect();
+    swarm.kill(&spawns[1]);
+    assert!(selection.contains(&spawns[2]));
+    assert!(targets.keys().map(Spawn::id).eq(0..4));
+
+    assert_eq!(Spawn::from_bits(bits[3], &swarm), Some(spawns[3].mirror()));
+    assert_eq!(Spawn::from_bits(bits[1], &swarm), None);
+    assert_eq!(Spawn::from_bits(u32::MAX as u64, &swarm), None);
+
+    // a recycled spawn has a new encoding
+    let reused = swarm.spawn().unwrap();
+    assert_eq!(reused, spawns[1]);
+    assert_ne!(reused.to_bits(), bits[1]);
+    assert_eq!(Spawn::from_bits(reused.to_bits(), &swarm), Some(reused));
+
+    // the number of a spawn taken after it was killed does not reach the next spawn of its slot
+    swarm.kill(&spawns[2]);
+    let killed_bits = spawns[2].to_bits();
+    let reused = swarm.spawn().unwrap();
+    assert_eq!(reused, spawns[2]);
+    assert_eq!(Spawn::from_bits(killed_bits, &swarm), None);
+}
+
+
+// integrity tests
+
+#[test]
+fn kill_all_and_double_kills_keep_the_swarm_consistent() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(4, SwarmData { counter: 0 });
+    let a = swarm.spawn().unwrap();
+    let b = swarm.spawn().unwrap();
+    swarm.spawn();
+    swarm.add_to_group(&b, 0);
+    swarm.kill(&a);
+    swarm.kill(&a);
+    assert_eq!(swarm.count(), 2);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+
+    // all used slots are recycled before new ones are constructed
+    swarm.kill_all();
+    assert_eq!(swarm.check_integrity(), Ok(()));
+    let spawns: Vec<Spawn> = (0..4).map(|_| swarm.spawn().unwrap()).collect();
+    assert_eq!(swarm.check_integrity(), Ok(()));
+    assert!(spawns.iter().enumerate().all(|(pos, s)| s.pos() == pos && s.active()));
+    assert!(swarm.spawn().is_none());
+
+    // a corrupted spawn is reported
+    spawns[1].0.borrow_mut().pos = 3;
+    assert_eq!(swarm.check_integrity(), Err(IntegrityError::Position { pos: 1, points_to: 3 }));
+}
+
+#[test]
+fn panicking_update_handlers_leave_the_swarm_consistent() {
+    let mut swarm = Swarm::<usize, ()>::new(8, ());
+    let spawns: Vec<Spawn> = (0..4).map(|i| swarm.spawn_with(i).unwrap()).collect();
+
+    // the handler kills the first object and panics on the second one
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        swarm.update(|ctl| {
+            if *ctl.target() == 0 { ctl.kill_current(); }
+            if *ctl.target() == 3 { panic!("handler failed"); }
+        });
+    }));
+    assert!(result.is_err());
+    assert_eq!(swarm.count(), 3);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+    assert!(!spawns[0].active());
+
+    // spawning and killing by spawn still work afterwards
+    swarm.kill(&spawns[1]);
+    let spawn = swarm.spawn_with(4).unwrap();
+    assert_eq!(*swarm.fetch_ref(&spawn), 4);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+}
+
+#[test]
+fn debug_lists_active_and_free_slots() {
+    let mut swarm = Swarm::<usize, ()>::new(4, ());
+    let a = swarm.spawn_with(10).unwrap();
+    swarm.spawn_with(11);
+    swarm.spawn_with(12);
+    swarm.kill(&a);
+
+    assert_eq!(
+        format!("{:?}", swarm), 
+        "Swarm { len: 2, capacity: 4, ticks: 0, active: {2: 12, 1: 11}, free: [0], .. }"
+    );
+}
+
+
+// shrink tests
+
+#[test]
+fn shrinking_keeps_spawned_instances_and_their_ids() {
+    let mut swarm = Swarm::<Minion, SwarmData>::new(16, SwarmData { counter: 0 });
+    let spawns: Vec<Spawn> = (0..16).map(|i| swarm.spawn_with(Minion { name: "", value: i }).unwrap()).collect();
+    for spawn in spawns.iter().filter(|s| ![1, 2, 7].contains(&s.id())) {
+        swarm.kill(spawn);
+    }
+    let released = spawns[12].downgrade();
+
+    assert_eq!(swarm.shrink_to(4), Err(ShrinkError { capacity: 4, min_capacity: 8 }));
+    assert_eq!(swarm.capacity(), 16);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+
+    swarm.shrink_to_fit();
+    assert_eq!(swarm.capacity(), 8);
+    assert_eq!(swarm.count(), 3);
+    assert_eq!(swarm.stats().free_len, 0);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+    for id in [1, 2, 7] {
+        assert_eq!(swarm.fetch_ref(&spawns[id]).value, id);
+        assert_eq!(Spawn::from_bits(spawns[id].to_bits(), &swarm), Some(spawns[id].mirror()));
+    }
+
+    // released slots stay dead, the remaining slots are constructed again
+    let new: Vec<Spawn> = (0..5).map(|_| swarm.spawn().unwrap()).collect();
+    assert!(swarm.spawn().is_none());
+    assert!(new.iter().all(|s| s.id() < 8 && swarm.fetch_ref(s).value == 0));
+    assert!(released.upgrade(&swarm).is_none());
+    assert_eq!(swarm.check_integrity(), Ok(()));
+}
+
+#[test]
+fn released_spawns_do_not_fetch_objects_spawned_after_shrinking() {
+    let mut swarm = Swarm::<usize, ()>::new(8, ());
+    let spawns: Vec<Spawn> = (0..6).map(|i| swarm.spawn_with(i).unwrap()).collect();
+    for spawn in &spawns[1..] { swarm.kill(spawn); }
+    swarm.shrink_to(4).unwrap();
+    while swarm.spawn_with(42).is_some() {}
+    assert_eq!(swarm.count(), 4);
+
+    // the objects of released spawns are gone, their spawns must not reach the new objects
+    for released in &spawns[4..] {
+        assert!(!released.active());
+        let fetched = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| *swarm.fetch(released)));
+        assert!(fetched.is_err());
+    }
+}
+
+
+// clone tests
+
+#[test]
+fn clones_have_independent_spawns() {
+    let mut swarm = Swarm::<Minion, ()>::new(8, ());
+    let spawns: Vec<Spawn> = (0..4).map(|i| swarm.spawn_with(Minion { name: "", value: i }).unwrap()).collect();
+    swarm.add_to_group(&spawns[3], 0);
+    let dead = spawns[0].downgrade();
+    swarm.kill(&spawns[0]);
+    let ghost = swarm.spawn_scoped().unwrap();
+    let ghost_id = ghost.id();
+    drop(ghost);
+
+    // the queued kill of the dropped guard is copied as well
+    let (mut clone, map) = swarm.clone_with_map();
+    clone.kill_dropped();
+    assert_eq!(clone.check_integrity(), Ok(()));
+    assert_eq!(clone.count(), 3);
+
+    // killing in the clone moves its spawns, the original ones stay where they are
+    let clone_spawns: Vec<Spawn> = spawns.iter().map(|s| map.translate(s).unwrap()).collect();
+    clone.kill(&clone_spawns[1]);
+    assert_eq!(clone_spawns[2].pos(), 1);
+    assert_eq!(spawns[2].pos(), 2);
+    assert_eq!(clone.fetch_ref(&clone_spawns[2]).value, 2);
+    assert_eq!(swarm.fetch_ref(&spawns[1]).value, 1);
+    assert!(clone.in_group(&clone_spawns[3], 0));
+    assert!(!clone_spawns.iter().any(|s| s.id() == ghost_id && s.active()));
+    swarm.kill_dropped();
+    assert_eq!(swarm.count(), 3);
+    assert_eq!(clone.check_integrity(), Ok(()));
+
+    // spawns of other swarms and killed weak spawns do not translate to live spawns
+    let other = Swarm::<Minion, ()>::new(8, ());
+    assert!(map.translate(&other.fetch_spawn(&0)).is_none());
+    assert!(map.translate_weak(&dead).unwrap().upgrade(&clone).is_none());
+    assert!(map.translate_weak(&spawns[2].downgrade()).unwrap().upgrade(&clone).is_some());
+}
+
+
+// rollback tests
+
+#[test]
+fn restored_states_resolve_spawns_to_the_same_objects() {
+    let mut swarm = Swarm::<Minion, usize>::new(8, 0);
+    let a = swarm.spawn_with(Minion { name: "a", value: 1 }).unwrap();
+    let b = swarm.spawn_with_ttl(3).unwrap();
+    swarm.add_to_group(&b, 0);
+    let mut state = swarm.save_state();
+
+    // the frames that are rolled back kill b and spawn c in its slot
+    let frame = |swarm: &mut Swarm<Minion, usize>| {
+        swarm.properties += 1;
+        swarm.kill(&b);
+        swarm.tick();
+        swarm.spawn_with(Minion { name: "c", value: 3 }).unwrap()
+    };
+    let c = frame(&mut swarm);
+    let (c_bits, c_weak) = (c.to_bits(), c.downgrade());
+    assert_eq!(c.id(), b.id());
+
+    swarm.restore_state(&state);
+    assert_eq!(swarm.check_integrity(), Ok(()));
+    assert_eq!((swarm.properties, swarm.ticks(), swarm.count()), (0, 0, 2));
+    assert!(b.active() && swarm.in_group(&b, 0));
+    assert_eq!(b.ttl(), Some(3));
+    assert_eq!(swarm.fetch_ref(&a).name, "a");
+    assert!(c_weak.upgrade(&swarm).is_none());
+
+    // replaying the frames spawns c with the same identity
+    let replayed = frame(&mut swarm);
+    assert_eq!(replayed.to_bits(), c_bits);
+    assert!(c_weak.upgrade(&swarm).is_some());
+
+    // saving into an existing state reuses its buffers
+    let pool = state.pool.as_ptr();
+    swarm.save_state_into(&mut state);
+    assert_eq!(state.pool.as_ptr(), pool);
+    assert_eq!(state.count(), 2);
+}
+
+
+// record and replay tests
+
+fn shield_factory(m: &mut Minion, _props: &mut SwarmData) {
+    m.name = "shield";
+}
+
+#[test]
+fn replaying_a_recording_rebuilds_the_same_spawns() {
+    let new_swarm = || {
+        let mut swarm = Swarm::<Minion, SwarmData>::new(16, SwarmData { counter: 0 });
+        swarm.add_factory(0, shield_factory);
+        swarm
+    };
+    let mut swarm = new_swarm();
+    swarm.populate(&[Minion { name: "", value: 1 }; 3]);
+    swarm.start_recording();
+
+    swarm.spawn_type(0);
+    swarm.populate(&[Minion { name: "", value: 2 }; 4]);
+    let bullet = swarm.spawn_with_ttl(2).unwrap();
+    swarm.update(|ctl| {
+        if ctl.target().value == 2 { ctl.kill_current(); }
+        if ctl.head() == 0 { ctl.spawn_with(Minion { name: "", value: 3 }); }
+    });
+    drop(swarm.spawn_scoped());
+    swarm.tick();
+    swarm.tick();
+    assert!(!bullet.active());
+    swarm.spawn();
+
+    let log = swarm.stop_recording().unwrap();
+    assert_eq!(log.ops()[..2], [Op::SpawnType { type_def: 0, id: 3 }, Op::Populate { count: 4 }]);
+    let decoded = OpLog::from_bytes(&log.to_bytes().unwrap()).unwrap();
+    assert_eq!(decoded, log);
+
+    let mut replayed = new_swarm();
+    replayed.populate(&[Minion { name: "", value: 1 }; 3]);
+    assert_eq!(replayed.replay(&decoded), Ok(()));
+    assert_eq!(replayed.check_integrity(), Ok(()));
+    assert_eq!(replayed.count(), swarm.count());
+    assert_eq!(replayed.ticks(), 2);
+    for pos in 0..swarm.count() {
+        assert_eq!(replayed.fetch_spawn(&pos).id(), swarm.fetch_spawn(&pos).id());
+    }
+    assert_eq!(replayed.fetch_ref(&replayed.fetch_spawn(&3)).name, "shield");
+
+    // replaying on a swarm in another state diverges
+    let mut other = new_swarm();
+    assert_eq!(other.replay(&log).unwrap_err().index, 0);
+    assert!(OpLog::from_bytes(&log.to_bytes().unwrap()[..10]).is_none());
+}
+
+fn encode_u32(value: &u32, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn decode_u32(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+#[test]
+fn replaying_recorded_values_rebuilds_the_same_objects() {
+    let mut swarm = Swarm::<u32, ()>::new(16, ());
+    swarm.populate(&[1, 2, 3]);
+    let start = swarm.save_state();
+    swarm.start_recording_with(encode_u32);
+
+    swarm.populate(&[4, 5]);
+    swarm.kill(&swarm.fetch_spawn(&0));
+    swarm.update(|ctl| if *ctl.target() == 5 { ctl.spawn_with(50); });
+    swarm.spawn_with(60);
+
+    let log = OpLog::from_bytes(&swarm.stop_recording().unwrap().to_bytes().unwrap()).unwrap();
+    assert_eq!(log.values().len(), 4);
+
+    let mut replayed = Swarm::<u32, ()>::new(16, ());
+    replayed.restore_state(&start);
+    assert_eq!(replayed.replay_with(&log, decode_u32), Ok(()));
+    assert_eq!(replayed.active_slice(), swarm.active_slice());
+    assert_eq!(replayed.check_integrity(), Ok(()));
+
+    // values that cannot be decoded, or are missing, diverge
+    replayed.restore_state(&start);
+    let error = replayed.replay_with(&log, |_| None).unwrap_err();
+    assert_eq!(error.op, Op::Populate { count: 2 });
+    let mut without_values = Swarm::<u32, ()>::new(16, ());
+    without_values.restore_state(&start);
+    without_values.start_recording();
+    without_values.spawn_with(1);
+    let unvalued = without_values.stop_recording().unwrap();
+    without_values.restore_state(&start);
+    assert!(without_values.replay_with(&unvalued, decode_u32).is_err());
+    without_values.restore_state(&start);
+    assert_eq!(without_values.replay(&unvalued), Ok(()));
+
+    // numbers that do not fit the format are not truncated
+    if usize::MAX > u32::MAX as usize {
+        swarm.start_recording();
+        let spawn = swarm.spawn().unwrap();
+        swarm.set_ttl(&spawn, Some(u32::MAX as usize + 1));
+        assert!(swarm.stop_recording().unwrap().to_bytes().is_none());
+    }
+}
+
+
+// model based tests, random operations are checked against a plain Vec model of the swarm
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Probe {
+    key: usize,     // unique for every spawned object
+    hits: usize,    // the number of times the object was touched
+}
+
+#[derive(Default, Clone)]
+pub struct ModelProps {
+    next_key: usize,
+    kill_modulo: usize,
+    visits: usize,
+    found: Option<usize>,
+    stopped: Option<usize>,
+}
+
+// xorshift64, deterministic for a given seed
+struct ModelRng(u64);
+
+impl ModelRng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+fn probe_factory(probe: &mut Probe, props: &mut ModelProps) {
+    *probe = Probe { key: props.next_key, hits: 0 };
+    props.next_key += 1;
+}
+
+struct Model {
+    swarm: Swarm<Probe, ModelProps>,
+    live: Vec<Probe>,                           // the objects the swarm should hold
+    handles: Vec<(Spawn, WeakSpawn, usize)>,    // handed out spawns and the key they were spawned with
+}
+
+impl Model {
+    fn new(capacity: usize) -> Self {
+        let mut swarm = Swarm::new(capacity, ModelProps::default());
+        swarm.add_factory(0, probe_factory);
+        Model { swarm, live: Vec::new(), handles: Vec::new() }
+    }
+
+    fn is_live(&self, key: usize) -> bool {
+        self.live.iter().any(|p| p.key == key)
+    }
+
+    fn spawned(&mut self, spawn: Option<Spawn>, ctx: &str) {
+        let full = self.live.len() == self.swarm.capacity();
+        assert_eq!(spawn.is_none(), full, "{}: spawn result", ctx);
+
+        if let Some(spawn) = spawn {
+            let probe = self.swarm.fetch_ref(&spawn).clone();
+            assert!(!self.is_live(probe.key), "{}: key {} spawned twice", ctx, probe.key);
+            self.handles.push((spawn.mirror(), spawn.downgrade(), probe.key));
+            self.live.push(probe);
+        }
+    }
+
+    fn step(&mut self, rng: &mut ModelRng, ctx: &str) {
+        match rng.below(13) {
+            0..=2 => {
+                let key = self.swarm.properties.next_key;
+                self.swarm.properties.next_key += 1;
+                let spawn = self.swarm.spawn_with(Probe { key, hits: 0 });
+                self.spawned(spawn, ctx);
+            },
+            3 => {
+                let spawn = self.swarm.spawn_type(0);
+                self.spawned(spawn, ctx);
+                assert!(self.swarm.spawn_type(1).is_none(), "{}: unknown spawn type", ctx);
+            },
+            4 if !self.handles.is_empty() => {
+                let (_, weak, key) = &self.handles[rng.below(self.handles.len())];
+                let key = *key;
+
+                // killed handles may point to a recycled slot, they must not upgrade
+                match weak.upgrade(&self.swarm) {
+                    Some(spawn) => {
+                        assert!(self.is_live(key), "{}: killed key {} upgraded", ctx, key);
+                        self.swarm.kill(&spawn);
+                        self.live.retain(|p| p.key != key);
+                    },
+                    None => assert!(!self.is_live(key), "{}: live key {} did not upgrade", ctx, key),
+                }
+            },
+            5 if !self.handles.is_empty() => {
+                // a plain spawn kills whatever object occupies its slot, inactive spawns are ignored
+                let spawn = self.handles[rng.below(self.handles.len())].0.mirror();
+                let occupant = spawn.active().then(|| self.swarm.fetch_ref(&spawn).key);
+                self.swarm.kill(&spawn);
+                self.live.retain(|p| Some(p.key) != occupant);
+            },
+            6 | 7 if !self.handles.is_empty() => {
+                let (spawn, weak, key) = &self.handles[rng.below(self.handles.len())];
+                if self.is_live(*key) {
+                    assert!(weak.upgrade(&self.swarm).is_some(), "{}: live key {} did not upgrade", ctx, key);
+                    let probe = self.swarm.fetch(spawn);
+                    probe.hits += 1;
+                    let probe = probe.clone();
+                    let expected = self.live.iter_mut().find(|p| p.key == *key).unwrap();
+                    expected.hits += 1;
+                    assert_eq!(&probe, expected, "{}: fetched object", ctx);
+                }
+            },
+            8 | 9 => {
+                self.swarm.properties.kill_modulo = 2 + rng.below(4);
+                self.swarm.properties.visits = 0;
+                self.swarm.update(|ctl| {
+                    ctl.properties.visits += 1;
+                    if ctl.target().key % ctl.properties.kill_modulo == 0 {
+                        ctl.kill_current();
+                    } else {
+                        ctl.target().hits += 1;
+                    }
+                });
+
+                let modulo = self.swarm.properties.kill_modulo;
+                assert_eq!(self.swarm.properties.visits, self.live.len(), "{}: update visits", ctx);
+                self.live.retain(|p| p.key % modulo != 0);
+                self.live.iter_mut().for_each(|p| p.hits += 1);
+            },
+            10 if !self.live.is_empty() => {
+                self.swarm.properties.kill_modulo = 2 + rng.below(4);
+                self.swarm.update(|ctl| {
+                    if ctl.head() != 0 { return; }
+                    let modulo = ctl.properties.kill_modulo;
+                    let found = ctl.find(|p| p.key % modulo == 1);
+                    let stopped = ctl.for_while(|p| p.key % modulo != 1);
+                    ctl.properties.found = found.map(|s| ctl.fetch_ref(&s).key);
+                    ctl.properties.stopped = stopped.map(|s| ctl.fetch_ref(&s).key);
+                });
+
+                let modulo = self.swarm.properties.kill_modulo;
+                let props = &self.swarm.properties;
+                assert_eq!(props.found, props.stopped, "{}: find and for_while disagree", ctx);
+                assert_eq!(props.found.is_some(), self.live.iter().any(|p| p.key % modulo == 1), "{}: find", ctx);
+                if let Some(key) = props.found {
+                    assert!(key % modulo == 1 && self.is_live(key), "{}: found key {}", ctx, key);
+                }
+            },
+            11 if rng.below(8) == 0 => {
+                self.swarm.kill_all();
+                self.live.clear();
+            },
+            12 if rng.below(16) == 0 => {
+                self.swarm.shrink_to_fit();
+                assert!(self.swarm.capacity() >= self.live.len(), "{}: shrunk below count", ctx);
+            },
+            _ => {},
+        }
+        if self.handles.len() > 64 {
+            self.handles.swap_remove(rng.below(self.handles.len()));
+        }
+    }
+
+    fn verify(&self, ctx: &str) {
+        assert_eq!(self.swarm.count(), self.live.len(), "{}: count", ctx);
+        assert_eq!(self.swarm.check_integrity(), Ok(()), "{}: integrity", ctx);
+
+        let mut actual = self.swarm.active_slice().to_vec();
+        let mut expected = self.live.clone();
+        actual.sort_by_key(|p| p.key);
+        expected.sort_by_key(|p| p.key);
+        assert_eq!(actual, expected, "{}: spawned objects", ctx);
+    }
+}
+
+fn run_model(seed: u64, capacity: usize, steps: usize) {
+    let mut rng = ModelRng(seed.max(1));
+    let mut model = Model::new(capacity);
+
+    for step in 0..steps {
+        let ctx = format!("seed {} capacity {} step {}", seed, capacity, step);
+        model.step(&mut rng, &ctx);
+        model.verify(&ctx);
+    }
+}
+
+/// Runs the model with many seeds, set SWARM_MODEL_SEED to replay a single failing seed
+#[test]
+fn random_operations_agree_with_the_model() {
+    let seeds: Vec<u64> = match std::env::var("SWARM_MODEL_SEED") {
+        Ok(seed) => vec![seed.parse().expect("SWARM_MODEL_SEED is not a number")],
+        Err(_) => (1..=32).collect(),
+    };
+
+    for seed in seeds {
+        run_model(seed, 1 + (seed as usize % 4) * 5, 1000);
+    }
+}
+
+
+// replication tests
+
+impl Diff for Probe {
+    type Patch = usize;
+
+    fn diff(&self, base: &Self) -> Option<usize> {
+        (self.hits != base.hits).then_some(self.hits)
+    }
+
+    fn apply(&mut self, hits: &usize) {
+        self.hits = *hits;
+    }
+}
+
+fn replicated_objects(swarm: &Swarm<Probe, ModelProps>) -> Vec<(u64, Probe)> {
+    let mut objects: Vec<(u64, Probe)> = (0..swarm.count())
+        .map(|pos| (swarm.fetch_spawn(&pos).to_bits(), swarm.active_slice()[pos].clone()))
+        .collect();
+    objects.sort_by_key(|(bits, _)| *bits);
+    objects
+}
+
+#[test]
+fn applied_deltas_reproduce_the_server_swarm() {
+    let mut rng = ModelRng(7);
+    let mut server = Model::new(12);
+    let mut client = Swarm::<Probe, ModelProps>::new(12, ModelProps::default());
+    let mut acked = server.swarm.save_state();
+
+    for step in 0..2000 {
+        let ctx = format!("step {}", step);
+        server.step(&mut rng, &ctx);
+
+        // the client acknowledges some of the deltas, the others are lost
+        if rng.below(4) == 0 {
+            let delta = server.swarm.delta(&acked);
+            if rng.below(3) == 0 { continue; }
+
+            // malformed deltas, listing an id twice or outside of the swarm, change nothing
+            let before = replicated_objects(&client);
+            let malformed = |change: &dyn Fn(&mut SwarmDelta<Probe>)| {
+                let mut malformed = delta.clone();
+                change(&mut malformed);
+                malformed
+            };
+            let mut bad_deltas = vec![
+                malformed(&|d| d.killed.push(12)),
+                malformed(&|d| d.spawned.push((12, 0, Probe::default()))),
+                malformed(&|d| d.changed.push((12, 0))),
+            ];
+            if let Some(id) = delta.killed.first() {
+                bad_deltas.push(malformed(&|d| d.killed.push(*id)));
+            }
+            if let Some(spawned) = delta.spawned.first() {
+                bad_deltas.push(malformed(&|d| d.spawned.push(spawned.clone())));
+            }
+            if let Some(changed) = delta.changed.first() {
+                bad_deltas.push(malformed(&|d| d.changed.push(*changed)));
+            }
+            for bad in &bad_deltas {
+                assert!(client.apply_delta(bad).is_err(), "{}: {:?}", ctx, bad);
+                assert_eq!(replicated_objects(&client), before, "{}", ctx);
+            }
+
+            assert_eq!(client.apply_delta(&delta), Ok(()), "{}", ctx);
+            assert_eq!(client.check_integrity(), Ok(()), "{}", ctx);
+            assert_eq!(replicated_objects(&client), replicated_objects(&server.swarm), "{}", ctx);
+            acked = server.swarm.save_state();
+
+            // a delta that was applied already does not match anymore, unless it only respawns
+            let respawned = |id: &usize| delta.spawned.iter().any(|(spawned, _, _)| spawned == id);
+            if delta.killed.iter().any(|id| !respawned(id)) || delta.spawned.len() > delta.killed.len() {
+                assert!(client.apply_delta(&delta).is_err(), "{}", ctx);
+                assert_eq!(replicated_objects(&client), replicated_objects(&server.swarm), "{}", ctx);
+            }
+        }
+    }
+}
+
+fn encode_probe(probe: &Probe, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&(probe.key as u64).to_le_bytes());
+    bytes.extend_from_slice(&(probe.hits as u64).to_le_bytes());
+}
+
+fn decode_probe(bytes: &[u8]) -> Option<Probe> {
+    if bytes.len() != 16 { return None; }
+    let key = u64::from_le_bytes(bytes[..8].try_into().ok()?) as usize;
+    let hits = u64::from_le_bytes(bytes[8..].try_into().ok()?) as usize;
+    Some(Probe { key, hits })
+}
+
+#[test]
+fn recorded_deltas_replay_on_the_client() {
+    let mut server = Swarm::<Probe, ModelProps>::new(8, ModelProps::default());
+    let mut client = Swarm::<Probe, ModelProps>::new(8, ModelProps::default());
+    let start = client.save_state();
+    client.start_recording_with(encode_probe);
+
+    let mut acked = server.save_state();
+    let spawns: Vec<Spawn> = (0..5).map(|key| server.spawn_with(Probe { key, hits: key * 10 }).unwrap()).collect();
+    server.kill(&spawns[1]);
+    client.apply_delta(&server.delta(&acked)).unwrap();
+    acked = server.save_state();
+
+    server.kill(&spawns[3]);
+    server.kill(&spawns[0]);
+    server.spawn_with(Probe { key: 5, hits: 50 });
+    client.apply_delta(&server.delta(&acked)).unwrap();
+
+    // a local spawn after the deltas takes the slot the client recycles first
+    client.spawn_with(Probe { key: 6, hits: 60 });
+
+    let log = client.stop_recording().unwrap();
+    assert!(log.ops().contains(&Op::SpawnAs { id: 2, gen: 0 }));
+    let log = OpLog::from_bytes(&log.to_bytes().unwrap()).unwrap();
+
+    let mut replayed = Swarm::<Probe, ModelProps>::new(8, ModelProps::default());
+    replayed.restore_state(&start);
+    assert_eq!(replayed.replay_with(&log, decode_probe), Ok(()));
+    assert_eq!(replayed.check_integrity(), Ok(()));
+    assert_eq!(replicated_objects(&replayed), replicated_objects(&client));
+}
+
+
+// change tracking tests
+
+#[test]
+fn tracked_changes_keep_a_renderer_in_sync() {
+    let mut rng = ModelRng(11);
+    let mut model = Model::new(12);
+    model.swarm.track_changes(true);
+    let mut rendered = BTreeMap::new();
+
+    for step in 0..2000 {
+        let ctx = format!("step {}", step);
+        let frame = model.swarm.ticks();
+        model.step(&mut rng, &ctx);
+        match rng.below(8) {
+            0 => {
+                model.swarm.for_each(|p| p.hits += 1);
+                model.live.iter_mut().for_each(|p| p.hits += 1);
+            },
+            1 if model.swarm.count() > 0 => {
+                let key = model.swarm.active_slice()[0].key;
+                model.swarm.fetch_raw(&0).hits += 1;
+                model.live.iter_mut().filter(|p| p.key == key).for_each(|p| p.hits += 1);
+            },
+            _ => {},
+        }
+
+        // the renderer only hears about removed, added and changed objects
+        for id in model.swarm.iter_removed_since(frame) {
+            rendered.remove(&id);
+        }
+        let changed: Vec<usize> = model.swarm.iter_changed_since(frame).map(|(s, _)| s.id()).collect();
+        for spawn in model.swarm.iter_added_since(frame) {
+            assert!(changed.contains(&spawn.id()), "{}: added spawn {} is not changed", ctx, spawn.id());
+        }
+        for (spawn, probe) in model.swarm.iter_changed_since(frame) {
+            rendered.insert(spawn.id(), probe.clone());
+        }
+        model.swarm.clear_changed();
+        model.swarm.tick();
+
+        let actual: BTreeMap<usize, Probe> = (0..model.swarm.count())
+            .map(|pos| (model.swarm.fetch_spawn(&pos).id(), model.swarm.active_slice()[pos].clone()))
+            .collect();
+        assert_eq!(rendered, actual, "{}", ctx);
+        assert_eq!(model.swarm.iter_changed_since(0).count(), 0, "{}", ctx);
+    }
+}
+
+// codec tests
+
+// truncated input has to be rejected, corrupted and random input must never panic
+fn fuzz_decoder<T>(bytes: &[u8], decode: impl Fn(&[u8]) -> Result<T, CodecError>) {
+    let mut rng = ModelRng(3);
+
+    for len in 0..bytes.len() {
+        assert!(decode(&bytes[..len]).is_err(), "truncated to {} bytes", len);
+    }
+    for pos in 0..bytes.len() {
+        for _ in 0..16 {
+            let mut corrupted = bytes.to_vec();
+            corrupted[pos] = rng.below(256) as u8;
+            let _ = decode(&corrupted);
+        }
+    }
+    for _ in 0..1000 {
+        let garbage: Vec<u8> = (0..rng.below(bytes.len() * 2)).map(|_| rng.below(256) as u8).collect();
+        let _ = decode(&garbage);
+
+        // garbage behind a valid header
+        let mut headed = bytes[..5].to_vec();
+        headed.extend_from_slice(&garbage);
+        let _ = decode(&headed);
+    }
+}
+
+#[test]
+fn byte_strs_survive_encoding_and_reject_corrupted_input() {
+    for text in &["", "orc", "a longer name", "héllo wörld, this needs 32 bytes", &"x".repeat(64)] {
+        let byte_str = ByteStr::from(*text);
+        let mut bytes = Vec::new();
+        byte_str.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes[..5], *b"SWBS\x01");
+        assert_eq!(ByteStr::read_from(&mut bytes.as_slice()).unwrap(), byte_str);
+
+        // decoded strings can always be turned into a String
+        fuzz_decoder(&bytes, |b| ByteStr::read_from(&mut &b[..]).map(|s| -> String { s.into() }));
+    }
+
+    let mut bytes = Vec::new();
+    ByteStr::from("orc").write_to(&mut bytes).unwrap();
+    let corrupt = |pos: usize, value: u8| {
+        let mut corrupted = bytes.clone();
+        corrupted[pos] = value;
+        ByteStr::read_from(&mut corrupted.as_slice())
+    };
+    assert!(matches!(corrupt(0, b'X'), Err(CodecError::Magic)));
+    assert!(matches!(corrupt(4, 2), Err(CodecError::Version(2))));
+    assert!(matches!(corrupt(5, 9), Err(CodecError::Corrupt(_))));
+    assert!(matches!(corrupt(6, 0xff), Err(CodecError::Corrupt(_))));
+}
+
+#[test]
+fn sized_pools_survive_encoding_and_reject_corrupted_input() {
+    let values: Vec<u64> = (0..40).map(|i| i * 0x0101_0101_0101).collect();
+    let pool = SizedPool64::<u64>::from_slice(&values);
+    let mut bytes = Vec::new();
+    sized_pool::write_to(&pool, &mut bytes).unwrap();
+    assert_eq!(bytes.len(), 5 + 12 + 40 * 8);
+
+    let read: SizedPool64<u64> = sized_pool::read_from(&mut bytes.as_slice()).unwrap();
+    let read_values: Vec<u64> = (0..*sized_pool::count(&read)).map(|i| sized_pool::get_ref(&read, i).unwrap()).collect();
+    assert_eq!(read_values, values);
+    fuzz_decoder(&bytes, |b| sized_pool::read_from::<u64, SizedPool64<u64>>(&mut &b[..]));
+
+    // a count beyond the capacity, and items that are not a bool
+    let flags = SizedPool16::<bool>::from_slice(&[true, false, true]);
+    let mut bytes = Vec::new();
+    sized_pool::write_to(&flags, &mut bytes).unwrap();
+    fuzz_decoder(&bytes, |b| sized_pool::read_from::<bool, SizedPool16<bool>>(&mut &b[..]));
+
+    let corrupt = |pos: usize, value: u8| {
+        let mut corrupted = bytes.clone();
+        corrupted[pos] = value;
+        sized_pool::read_from::<bool, SizedPool16<bool>>(&mut corrupted.as_slice())
+    };
+    assert!(corrupt(13, 16).is_err());
+    assert!(matches!(corrupt(13, 17), Err(CodecError::Corrupt(_))));
+    assert!(matches!(corrupt(18, 2), Err(CodecError::Corrupt(_))));
+    assert!(matches!(corrupt(9, 4), Err(CodecError::Corrupt(_))));
+}
+
+// shared tests, these run for both the heap Swarm and the StaticSwarm
+
+macro_rules! shared_swarm_tests {
+    ($suite:ident, $new_swarm:expr) => {
+        mod $suite {
+            use super::*;
+
+            #[test]
+            fn spawns_fetch_their_own_objects() {
+                let mut swarm = $new_swarm(SwarmData { counter: 0 });
+                let a = swarm.spawn().unwrap();
+                let b = swarm.spawn().unwrap();
+                swarm.fetch(&a).value = 1;
+                swarm.fetch(&b).value = 2;
+
+                assert_eq!(swarm.count(), 2);
+                assert_eq!(swarm.capacity(), 16);
+                assert_eq!(swarm.fetch_ref(&a).value, 1);
+                assert_eq!(swarm.fetch_ref(&b).value, 2);
+            }
+
+            #[test]
+            fn spawning_fails_when_the_pool_is_full() {
+                let mut swarm = $new_swarm(SwarmData { counter: 0 });
+                for _i in 0..16 { assert!(swarm.spawn().is_some()); }
+                assert!(swarm.spawn().is_none());
+                assert!(swarm.spawn_with(Minion::default()).is_none());
+
+                let first = swarm.fetch_spawn(&0);
+                swarm.kill(&first);
+                assert!(swarm.spawn().is_some());
+                assert_eq!(swarm.count(), 16);
+            }
+
+            #[test]
+            fn killed_objects_are_not_iterated() {
+                let mut swarm = $new_swarm(SwarmData { counter: 0 });
+                let spawns: Vec<_> = (0..5).map(|_| swarm.spawn().unwrap()).collect();
+                swarm.kill(&spawns[1]);
+                swarm.kill(&spawns[3]);
+
+                swarm.for_each(|m| m.add_one());
+                swarm.for_all(|pos, list, props| {
+                    props.counter += list.len();
+                    list[*pos].add_one();
+                });
+
+                assert_eq!(swarm.properties.counter, 9);
+                assert_eq!(swarm.active_slice().len(), 3);
+                assert!(swarm.active_slice().iter().all(|m| m.value == 2));
+                for i in [0, 2, 4].iter() {
+                    assert_eq!(swarm.fetch_ref(&spawns[*i]).value, 2);
+                }
+            }
+
+            #[test]
+            fn killed_slots_are_recycled() {
+                let mut swarm = $new_swarm(SwarmData { counter: 0 });
+                let a = swarm.spawn().unwrap();
+                let b = swarm.spawn().unwrap();
+                swarm.fetch(&b).value = 2;
+                swarm.kill(&a);
+
+                let c = swarm.spawn_with(Minion { name: "c", value: 3 }).unwrap();
+                assert_eq!(swarm.count(), 2);
+                assert_eq!(swarm.fetch_ref(&b).value, 2);
+                assert_eq!(swarm.fetch_ref(&c).value, 3);
+
+                swarm.kill_all();
+                assert_eq!(swarm.count(), 0);
+                assert!(swarm.spawn().is_some());
+            }
+
+            #[test]
+            fn update_visits_every_spawn_once() {
+                let mut swarm = $new_swarm(SwarmData { counter: 0 });
+                for i in 0..10 {
+                    swarm.spawn_with(Minion { name: "", value: i });
+                }
+
+                // even spawns are killed, one spawn is created during the loop
+                swarm.update(|ctl| {
+                    ctl.properties.counter += 1;
+                    let value = ctl.target().value;
+                    if value % 2 == 0 { ctl.kill_current(); }
+                    if value == 1 { ctl.spawn_with(Minion { name: "new", value: 100 }); }
+                });
+
+                assert_eq!(swarm.properties.counter, 10);
+                assert_eq!(swarm.count(), 6);
+                let mut values: Vec<usize> = swarm.active_slice().iter().map(|m| m.value).collect();
+                values.sort();
+                assert_eq!(values, vec![1, 3, 5, 7, 9, 100]);
+            }
+        }
+    };
+}
+
+shared_swarm_tests!(heap_swarm, |properties| Swarm::<Minion, SwarmData>::new(16, properties));
+shared_swarm_tests!(static_swarm, StaticSwarm::<Minion, SwarmData, 16>::new);
+
+#[test]
+#[should_panic]
+fn static_swarms_do_not_fetch_killed_spawns() {
+    let mut swarm = StaticSwarm::<Minion, (), 4>::new(());
+    let first = swarm.spawn().unwrap();
+    swarm.kill(&first);
+
+    // the slot is spawned again, the old handle must not reach the new object
+    swarm.spawn().unwrap();
+    swarm.fetch_ref(&first);
+}
@@ -11,8 +11,14 @@
 use std::str;
 use std::convert::From;
 use std::fmt;
+use std::io::{ self, Read, Write };
 //use std::fmt::Debug;
 
+use super::codec::{ self, CodecError };
+
+// the magic bytes of an encoded ByteStr, see the codec module
+const MAGIC: &[u8; 4] = b"SWBS";
+
 /// Sized byte string with predefined sizes of 8, 16, 32 or 64 characters/bytes
 /// 
 /// Examples
@@ -82,6 +88,65 @@ impl ByteStr {
             ByteStr::Str64(s) => s[at_index],
         }
     }
+
+    /// Write the ByteStr in the binary format of the codec module: a header, the
+    /// byte length of the ByteStr as a single byte, followed by all of its bytes.
+    /// 
+    /// Examples
+    /// ```
+    /// use swarm_pool::tools::byte_str::ByteStr;
+    /// 
+    /// let mut bytes = Vec::new();
+    /// ByteStr::from("test").write_to(&mut bytes).unwrap();
+    /// assert_eq!(bytes.len(), 5 + 1 + 8);
+    /// 
+    /// let read = ByteStr::read_from(&mut bytes.as_slice()).unwrap();
+    /// assert_eq!(read, "test");
+    /// assert_eq!(read.len(), 8);
+    /// ```
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let bytes = self.bytes();
+        codec::write_header(writer, MAGIC)?;
+        writer.write_all(&[bytes.len() as u8])?;
+        writer.write_all(bytes)
+    }
+
+    /// Read a ByteStr written by `write_to()`. Returns a CodecError if the input was 
+    /// truncated, has an unknown length, or the string is not valid UTF-8.
+    pub fn read_from(reader: &mut impl Read) -> Result<ByteStr, CodecError> {
+        codec::read_header(reader, MAGIC)?;
+        let mut len = [0; 1];
+        reader.read_exact(&mut len)?;
+
+        let byte_str = match len[0] {
+            8 => ByteStr::Str8(read_array(reader)?),
+            16 => ByteStr::Str16(read_array(reader)?),
+            32 => ByteStr::Str32(read_array(reader)?),
+            64 => ByteStr::Str64(read_array(reader)?),
+            _ => return Err(CodecError::Corrupt("unknown ByteStr length")),
+        };
+
+        // only the bytes up to the first zero are part of the string
+        let bytes = byte_str.bytes();
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        str::from_utf8(&bytes[..end]).map_err(|_| CodecError::Corrupt("ByteStr is not valid UTF-8"))?;
+        Ok(byte_str)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self {
+            ByteStr::Str8(s) => s,
+            ByteStr::Str16(s) => s,
+            ByteStr::Str32(s) => s,
+            ByteStr::Str64(s) => s,
+        }
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut array = [0; N];
+    reader.read_exact(&mut array)?;
+    Ok(array)
 }
 
 /// Default ByteStr is ByteStr::Str8([0; 8])
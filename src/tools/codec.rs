@@ -0,0 +1,139 @@
+//! A compact, versioned binary format for the Sized tools.
+//!
+//! Every encoded value starts with a header of four magic bytes that name the type,
+//! followed by a one byte format version. All numbers are stored little endian, so
+//! encoded values can be exchanged between machines. Decoding never panics, truncated
+//! or corrupted input results in a CodecError.
+//!
+//! Pool items are encoded by the FixedCodec trait, which is implemented for the integer,
+//! float and bool types.
+//!
+//! Example
+//! ```
+//! use swarm_pool::tools::byte_str::ByteStr;
+//! use swarm_pool::tools::codec::CodecError;
+//!
+//! let mut bytes = Vec::new();
+//! ByteStr::from("orc").write_to(&mut bytes).unwrap();
+//! assert_eq!(ByteStr::read_from(&mut bytes.as_slice()).unwrap(), "orc");
+//!
+//! // truncated input is an error
+//! let result = ByteStr::read_from(&mut &bytes[..6]);
+//! assert!(matches!(result, Err(CodecError::Io(_))));
+//! ```
+
+use std::convert::{ TryFrom, TryInto };
+use std::fmt;
+use std::io::{ self, Read, Write };
+
+/// The format version written by this version of the library
+pub const VERSION: u8 = 1;
+
+/// A value that is encoded in a fixed number of bytes, see the module documentation
+pub trait FixedCodec: Sized {
+    /// The number of bytes of an encoded value
+    const SIZE: usize;
+
+    /// Encode the value into `bytes`, which holds exactly SIZE bytes
+    fn encode(&self, bytes: &mut [u8]);
+
+    /// Decode a value from `bytes`, which holds exactly SIZE bytes.
+    /// Returns None if the bytes are not a valid value.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! fixed_codec_le {
+    ($($t:ty),*) => {$(
+        impl FixedCodec for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+
+            fn encode(&self, bytes: &mut [u8]) {
+                bytes.copy_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(bytes: &[u8]) -> Option<Self> {
+                Some(<$t>::from_le_bytes(bytes.try_into().ok()?))
+            }
+        }
+    )*};
+}
+
+fixed_codec_le!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl FixedCodec for bool {
+    const SIZE: usize = 1;
+
+    fn encode(&self, bytes: &mut [u8]) {
+        bytes[0] = *self as u8;
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+/// Returned when decoding fails
+#[derive(Debug)]
+pub enum CodecError {
+    /// Reading failed, truncated input results in an UnexpectedEof error
+    Io(io::Error),
+    /// The input does not start with the magic bytes of the expected type
+    Magic,
+    /// The input was written in a format version this library cannot read
+    Version(u8),
+    /// The input is corrupted, the message names what is wrong with it
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "reading failed: {}", e),
+            CodecError::Magic => write!(f, "the input is not of the expected type"),
+            CodecError::Version(v) => write!(f, "unsupported format version {}", v),
+            CodecError::Corrupt(what) => write!(f, "corrupted input: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self { CodecError::Io(e) }
+}
+
+pub(crate) fn write_header(writer: &mut impl Write, magic: &[u8; 4]) -> io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&[VERSION])
+}
+
+pub(crate) fn read_header(reader: &mut impl Read, magic: &[u8; 4]) -> Result<(), CodecError> {
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+
+    if &header[..4] != magic { return Err(CodecError::Magic); }
+    if header[4] != VERSION { return Err(CodecError::Version(header[4])); }
+    Ok(())
+}
+
+pub(crate) fn write_u32(writer: &mut impl Write, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "value exceeds u32"))?;
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn read_u32(reader: &mut impl Read) -> Result<usize, CodecError> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes) as usize)
+}
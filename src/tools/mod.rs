@@ -6,5 +6,9 @@
 //! as awesome as the rest of this library, there are other libraries on crates.io
 //! that provide more complete and optimized solutions on Sized objects.
 
+#[allow(clippy::len_without_is_empty, clippy::clone_on_copy, clippy::manual_memcpy, clippy::from_over_into, clippy::map_clone)]
 pub mod byte_str;
+pub mod codec;
+#[allow(clippy::clone_on_copy, clippy::needless_return, clippy::map_flatten, clippy::needless_borrow, clippy::needless_range_loop)]
 pub mod sized_pool;
+pub mod spatial;
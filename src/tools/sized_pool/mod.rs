@@ -2,6 +2,9 @@
 //! 
 //! These pools come in 7 different capacity flavours: 16, 32, 64, 128, 256, 512 and 1024.
 //! These pools cannot exceed their maximum capacity. 
+//! 
+//! Pools of FixedCodec items can be stored in the binary format of the codec module,
+//! see `write_to()` and `read_from()`.
 
 mod pool16; 
 mod pool32;
@@ -19,6 +22,20 @@ pub use pool256::SizedPool256;
 pub use pool512::SizedPool512;
 pub use pool1024::SizedPool1024;
 
+use std::io::{ self, Read, Write };
+use super::codec::{ self, CodecError, FixedCodec };
+
+// the magic bytes of an encoded SizedPool, see the codec module
+const MAGIC: &[u8; 4] = b"SWSP";
+
+// the capacity of the pools of this module, used by write_to() and read_from(). It is
+// kept out of StackPool, so pools implemented elsewhere do not have to provide it.
+mod sealed {
+    pub trait Capacity {
+        const CAPACITY: usize;
+    }
+}
+
 /// The StackPool trait is used by this module to communicate with the different pool
 /// types on a polymorphic level. 
 /// 
@@ -490,3 +507,79 @@ where   ItemType: Copy + PartialEq,
         *pool.order_at(second_position) = first_order;
     //}
 }
+
+#[allow(unused)]
+/// Write all items of the pool in the binary format of the codec module: a header, 
+/// the capacity of the pool, the encoded size of an item and the number of items as 
+/// little endian u32 values, followed by the encoded items in pool order.
+/// 
+/// # Example
+/// ```
+/// use swarm_pool::tools::sized_pool::{ SizedPool16, SizedPool32 };
+/// use swarm_pool::tools::sized_pool;
+/// use swarm_pool::tools::codec::CodecError;
+/// 
+/// fn main() {
+///     let pool = SizedPool16::<u32>::from_slice(&[10, 20, 30]);
+///     let mut bytes = Vec::new();
+///     sized_pool::write_to(&pool, &mut bytes).unwrap();
+/// 
+///     let read: SizedPool16<u32> = sized_pool::read_from(&mut bytes.as_slice()).unwrap();
+///     assert_eq!(sized_pool::count(&read), &3);
+///     assert_eq!(sized_pool::get_ref(&read, 2), &Some(30));
+/// 
+///     // the pool capacity and item type have to match
+///     let result: Result<SizedPool32<u32>, _> = sized_pool::read_from(&mut bytes.as_slice());
+///     assert!(matches!(result, Err(CodecError::Corrupt(_))));
+/// }
+/// ```
+pub fn write_to<ItemType, Pool>(pool: &Pool, writer: &mut impl Write) -> io::Result<()>
+where   
+    ItemType: Copy + PartialEq + FixedCodec,
+    Pool: StackPool<ItemType> + sealed::Capacity, 
+{
+    let items: Vec<ItemType> = (0..*pool.count()).filter_map(|i| *pool.ref_sorted(&i)).collect();
+
+    codec::write_header(writer, MAGIC)?;
+    codec::write_u32(writer, Pool::CAPACITY)?;
+    codec::write_u32(writer, ItemType::SIZE)?;
+    codec::write_u32(writer, items.len())?;
+
+    let mut buffer = vec![0; ItemType::SIZE];
+    for item in items {
+        item.encode(&mut buffer);
+        writer.write_all(&buffer)?;
+    }
+    Ok(())
+}
+
+#[allow(unused)]
+/// Read a pool written by `write_to()`. Returns a CodecError if the input was truncated
+/// or corrupted, or if it was written by a pool with a different capacity or item type.
+pub fn read_from<ItemType, Pool>(reader: &mut impl Read) -> Result<Pool, CodecError>
+where   
+    ItemType: Copy + PartialEq + FixedCodec,
+    Pool: StackPool<ItemType> + sealed::Capacity + Default, 
+{
+    codec::read_header(reader, MAGIC)?;
+    let mut pool = Pool::default();
+
+    if codec::read_u32(reader)? != Pool::CAPACITY {
+        return Err(CodecError::Corrupt("the pool capacity does not match"));
+    }
+    if codec::read_u32(reader)? != ItemType::SIZE {
+        return Err(CodecError::Corrupt("the item size does not match"));
+    }
+    let count = codec::read_u32(reader)?;
+    if count > Pool::CAPACITY {
+        return Err(CodecError::Corrupt("the item count exceeds the pool capacity"));
+    }
+
+    let mut buffer = vec![0; ItemType::SIZE];
+    for _ in 0..count {
+        reader.read_exact(&mut buffer)?;
+        let item = ItemType::decode(&buffer).ok_or(CodecError::Corrupt("invalid item"))?;
+        push(&mut pool, item);
+    }
+    Ok(pool)
+}
@@ -87,6 +87,10 @@ where ItemType: Copy + PartialEq
     }
 }
 
+impl<ItemType> super::sealed::Capacity for SizedPool1024<ItemType> {
+    const CAPACITY: usize = CAPACITY;
+}
+
 impl<ItemType> StackPool<ItemType> for SizedPool1024<ItemType> {
     fn count(&self) -> &usize { 
         &self.count 
@@ -87,6 +87,10 @@ where ItemType: Copy + PartialEq
     }
 }
 
+impl<ItemType> super::sealed::Capacity for SizedPool128<ItemType> {
+    const CAPACITY: usize = CAPACITY;
+}
+
 impl<ItemType> StackPool<ItemType> for SizedPool128<ItemType> {
     fn count(&self) -> &usize { 
         &self.count 
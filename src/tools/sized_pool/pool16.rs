@@ -86,6 +86,10 @@ where ItemType: Copy + PartialEq
     }
 }
 
+impl<ItemType> super::sealed::Capacity for SizedPool16<ItemType> {
+    const CAPACITY: usize = CAPACITY;
+}
+
 impl<ItemType> StackPool<ItemType> for SizedPool16<ItemType> {
     fn count(&self) -> &usize { 
         &self.count 
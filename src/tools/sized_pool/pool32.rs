@@ -88,6 +88,10 @@ where ItemType: Copy + PartialEq
     }
 }
 
+impl<ItemType> super::sealed::Capacity for SizedPool32<ItemType> {
+    const CAPACITY: usize = CAPACITY;
+}
+
 impl<ItemType> StackPool<ItemType> for SizedPool32<ItemType> {
     fn count(&self) -> &usize { 
         &self.count 
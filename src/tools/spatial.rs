@@ -0,0 +1,293 @@
+//! A uniform grid index for neighbor queries over the spawned objects of a Swarm.
+//!
+//! Boids, collisions and area of effect checks all need to know "who is near me". Looping
+//! through the pool inside of another loop gets slow very quickly, the spatial grid sorts
+//! all spawned objects into cells so that only the cells near a point have to be checked.
+//!
+//! The grid stores object positions, these change when spawns are killed. The grid should
+//! therefore be rebuilt every frame (or after spawning or killing), rebuilding does not
+//! allocate once the grid has seen the largest number of spawns it has to index.
+//!
+//! Examples
+//! ```
+//! use swarm_pool::Swarm;
+//! use swarm_pool::tools::spatial::SpatialGrid;
+//!
+//! #[derive(Default, Clone)]
+//! pub struct Boid { x: f32, y: f32 }
+//!
+//! let mut swarm = Swarm::<Boid, _>::new(10, ());
+//! let a = swarm.spawn().unwrap();
+//! let b = swarm.spawn().unwrap();
+//! let c = swarm.spawn().unwrap();
+//! *swarm.fetch(&a) = Boid { x: 1.0, y: 1.0 };
+//! *swarm.fetch(&b) = Boid { x: 2.0, y: 1.0 };
+//! *swarm.fetch(&c) = Boid { x: 9.0, y: 9.0 };
+//!
+//! let mut grid = SpatialGrid::new((0.0, 0.0), (10.0, 10.0), 2.0, |boid: &Boid| (boid.x, boid.y));
+//! grid.rebuild(&swarm);
+//!
+//! let near_a = grid.query_radius(&swarm, (1.0, 1.0), 1.5);
+//! assert_eq!(near_a.len(), 2);
+//! assert!(near_a.contains(&a) && near_a.contains(&b));
+//! ```
+
+use crate::{ Swarm, Spawn, ObjectPosition };
+
+/// A 2D point as (x, y)
+pub type Point = (f32, f32);
+
+/// A callback handler used by the SpatialGrid to get the position of a pool object.
+pub type PositionHandler<ItemType> = fn(&ItemType) -> Point;
+
+/// A uniform grid that indexes the spawned objects of a Swarm by their position.
+///
+/// Objects outside of the grid bounds are stored in the nearest edge cell, so they
+/// can still be found, but queries outside of the bounds become slower.
+pub struct SpatialGrid<ItemType> {
+    origin: Point,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    position: PositionHandler<ItemType>,
+
+    cell_start: Vec<usize>,         // per cell, the first index into entries
+    entries: Vec<ObjectPosition>,   // object positions sorted by cell
+    points: Vec<Point>,             // per object position, its location
+    cells: Vec<usize>,              // per object position, its cell
+    len: usize,
+}
+
+impl<ItemType> SpatialGrid<ItemType> {
+
+    /// Creates a new grid that covers the area from `origin` up to `origin + size`,
+    /// divided into square cells of `cell_size`. The `position` handler is used to
+    /// get the location of the pool objects when the grid is rebuilt.
+    ///
+    /// A good cell size is about the radius most queries are going to use.
+    pub fn new(origin: Point, size: Point, cell_size: f32, position: PositionHandler<ItemType>) -> Self {
+        let cell_size = if cell_size > 0.0 { cell_size } else { 1.0 };
+        let columns = ((size.0 / cell_size).ceil() as usize).max(1);
+        let rows = ((size.1 / cell_size).ceil() as usize).max(1);
+
+        SpatialGrid {
+            origin,
+            cell_size,
+            columns,
+            rows,
+            position,
+            cell_start: vec![0; columns * rows + 1],
+            entries: Vec::new(),
+            points: Vec::new(),
+            cells: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of objects that were indexed by the last rebuild
+    pub fn count(&self) -> usize { self.len }
+
+    /// Re-index all spawned objects of the swarm.
+    ///
+    /// This does not allocate, unless the swarm has more spawns than the grid has
+    /// indexed before.
+    pub fn rebuild<Properties>(&mut self, swarm: &Swarm<ItemType, Properties>) {
+        let len = swarm.len;
+
+        if self.points.len() < len {
+            self.points.resize(len, (0.0, 0.0));
+            self.cells.resize(len, 0);
+            self.entries.resize(len, 0);
+        }
+        self.len = len;
+
+        // count the number of objects per cell
+        let num_cells = self.columns * self.rows;
+        for start in self.cell_start.iter_mut() { *start = 0; }
+
+        for pos in 0..len {
+            let point = (self.position)(&swarm.pool[pos]);
+            let cell = self.cell_index(point);
+
+            self.points[pos] = point;
+            self.cells[pos] = cell;
+            self.cell_start[cell] += 1;
+        }
+
+        // turn the counts into cell end indices
+        let mut total = 0;
+        for start in self.cell_start.iter_mut().take(num_cells) {
+            total += *start;
+            *start = total;
+        }
+        self.cell_start[num_cells] = len;
+
+        // walking backwards turns the end indices into start indices
+        for pos in (0..len).rev() {
+            let cell = self.cells[pos];
+            self.cell_start[cell] -= 1;
+            self.entries[self.cell_start[cell]] = pos;
+        }
+    }
+
+    /// Returns all spawns within `radius` of `center`.
+    ///
+    /// # Example
+    /// ```
+    /// use swarm_pool::Swarm;
+    /// use swarm_pool::tools::spatial::SpatialGrid;
+    ///
+    /// let mut swarm = Swarm::<(f32, f32), _>::new(10, ());
+    /// swarm.populate(&[(1.0, 1.0), (4.0, 1.0), (8.0, 8.0)]);
+    ///
+    /// let mut grid = SpatialGrid::new((0.0, 0.0), (10.0, 10.0), 2.0, |p: &(f32, f32)| *p);
+    /// grid.rebuild(&swarm);
+    ///
+    /// assert_eq!(grid.query_radius(&swarm, (0.0, 0.0), 2.0).len(), 1);
+    /// assert_eq!(grid.query_radius(&swarm, (0.0, 0.0), 5.0).len(), 2);
+    /// assert_eq!(grid.query_radius(&swarm, (0.0, 0.0), 20.0).len(), 3);
+    /// ```
+    pub fn query_radius<Properties>(&self, swarm: &Swarm<ItemType, Properties>, center: Point, radius: f32) -> Vec<Spawn> {
+        let min = (center.0 - radius, center.1 - radius);
+        let max = (center.0 + radius, center.1 + radius);
+        let radius_sq = radius * radius;
+        let mut found = Vec::new();
+
+        self.visit_area(min, max, |pos, point| {
+            if distance_sq(point, center) <= radius_sq {
+                found.push(swarm.spawns[pos].mirror());
+            }
+        });
+        found
+    }
+
+    /// Returns all spawns inside of the axis aligned box from `min` to `max`.
+    ///
+    /// # Example
+    /// ```
+    /// use swarm_pool::Swarm;
+    /// use swarm_pool::tools::spatial::SpatialGrid;
+    ///
+    /// let mut swarm = Swarm::<(f32, f32), _>::new(10, ());
+    /// swarm.populate(&[(1.0, 1.0), (4.0, 1.0), (8.0, 8.0)]);
+    ///
+    /// let mut grid = SpatialGrid::new((0.0, 0.0), (10.0, 10.0), 2.0, |p: &(f32, f32)| *p);
+    /// grid.rebuild(&swarm);
+    ///
+    /// let found = grid.query_aabb(&swarm, (3.0, 0.0), (9.0, 9.0));
+    /// assert_eq!(found.len(), 2);
+    /// assert_eq!(*swarm.fetch_ref(&found[0]), (4.0, 1.0));
+    /// assert_eq!(*swarm.fetch_ref(&found[1]), (8.0, 8.0));
+    /// ```
+    pub fn query_aabb<Properties>(&self, swarm: &Swarm<ItemType, Properties>, min: Point, max: Point) -> Vec<Spawn> {
+        let mut found = Vec::new();
+
+        self.visit_area(min, max, |pos, point| {
+            if point.0 >= min.0 && point.0 <= max.0 && point.1 >= min.1 && point.1 <= max.1 {
+                found.push(swarm.spawns[pos].mirror());
+            }
+        });
+        found
+    }
+
+    /// Returns the `k` spawns nearest to `point`, ordered from near to far.
+    /// Fewer than `k` spawns are returned if the grid does not hold enough objects.
+    ///
+    /// # Example
+    /// ```
+    /// use swarm_pool::Swarm;
+    /// use swarm_pool::tools::spatial::SpatialGrid;
+    ///
+    /// let mut swarm = Swarm::<(f32, f32), _>::new(10, ());
+    /// swarm.populate(&[(1.0, 1.0), (4.0, 1.0), (8.0, 8.0)]);
+    ///
+    /// let mut grid = SpatialGrid::new((0.0, 0.0), (10.0, 10.0), 2.0, |p: &(f32, f32)| *p);
+    /// grid.rebuild(&swarm);
+    ///
+    /// let nearest = grid.nearest(&swarm, (9.0, 9.0), 2);
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(*swarm.fetch_ref(&nearest[0]), (8.0, 8.0));
+    /// assert_eq!(*swarm.fetch_ref(&nearest[1]), (4.0, 1.0));
+    /// ```
+    pub fn nearest<Properties>(&self, swarm: &Swarm<ItemType, Properties>, point: Point, k: usize) -> Vec<Spawn> {
+        let mut best: Vec<(f32, ObjectPosition)> = Vec::with_capacity(k + 1);
+        if k == 0 || self.len == 0 { return Vec::new(); }
+
+        let (column, row) = self.cell_coords(point);
+        let max_ring = self.columns.max(self.rows);
+        let mut ring = 0;
+
+        while ring <= max_ring {
+            // objects in this ring or further away are at least this far from the point
+            let reach = ring.saturating_sub(1) as f32 * self.cell_size;
+            if best.len() == k && best[k - 1].0 < reach * reach { break; }
+
+            self.visit_ring(column, row, ring, |pos| {
+                let d = distance_sq(self.points[pos], point);
+                if best.len() < k || d < best[best.len() - 1].0 {
+                    let at = best.iter().position(|b| d < b.0).unwrap_or(best.len());
+                    best.insert(at, (d, pos));
+                    best.truncate(k);
+                }
+            });
+            ring += 1;
+        }
+        best.iter().map(|(_, pos)| swarm.spawns[*pos].mirror()).collect()
+    }
+
+    fn cell_coords(&self, point: Point) -> (usize, usize) {
+        let column = ((point.0 - self.origin.0) / self.cell_size).floor();
+        let row = ((point.1 - self.origin.1) / self.cell_size).floor();
+
+        (
+            (column.max(0.0) as usize).min(self.columns - 1),
+            (row.max(0.0) as usize).min(self.rows - 1),
+        )
+    }
+
+    fn cell_index(&self, point: Point) -> usize {
+        let (column, row) = self.cell_coords(point);
+        row * self.columns + column
+    }
+
+    fn visit_cell<Visit: FnMut(ObjectPosition)>(&self, column: usize, row: usize, visit: &mut Visit) {
+        let cell = row * self.columns + column;
+        for pos in &self.entries[self.cell_start[cell]..self.cell_start[cell + 1]] {
+            visit(*pos);
+        }
+    }
+
+    fn visit_area<Visit: FnMut(ObjectPosition, Point)>(&self, min: Point, max: Point, mut visit: Visit) {
+        let (min_column, min_row) = self.cell_coords(min);
+        let (max_column, max_row) = self.cell_coords(max);
+
+        for row in min_row..=max_row {
+            for column in min_column..=max_column {
+                self.visit_cell(column, row, &mut |pos| visit(pos, self.points[pos]));
+            }
+        }
+    }
+
+    fn visit_ring<Visit: FnMut(ObjectPosition)>(&self, column: usize, row: usize, ring: usize, mut visit: Visit) {
+        let (column, row, ring) = (column as isize, row as isize, ring as isize);
+        let (columns, rows) = (self.columns as isize, self.rows as isize);
+
+        for r in (row - ring)..=(row + ring) {
+            if r < 0 || r >= rows { continue; }
+            let edge_row = r == row - ring || r == row + ring;
+
+            for c in (column - ring)..=(column + ring) {
+                if c < 0 || c >= columns { continue; }
+                if edge_row || c == column - ring || c == column + ring {
+                    self.visit_cell(c as usize, r as usize, &mut visit);
+                }
+            }
+        }
+    }
+}
+
+fn distance_sq(a: Point, b: Point) -> f32 {
+    let dx = a.0 - b.0;
+    let dy = a.1 - b.1;
+    dx * dx + dy * dy
+}
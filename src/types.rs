@@ -1,10 +1,17 @@
 //! Types used by the Swarm pool.
 
-use std::rc::Rc;
-use std::cell::RefCell;
-use std::fmt;
+#[cfg(feature = "alloc")]
+use alloc::rc::{ Rc, Weak };
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::{ cell::RefCell, cmp::Ordering, fmt, hash::{ Hash, Hasher } };
 
+#[cfg(feature = "alloc")]
 use super::control::SwarmControl;
+#[cfg(feature = "alloc")]
+use super::Swarm;
+use super::static_swarm::StaticControl;
 
 /// A pointer to a swarm data object
 pub type ObjectPosition = usize;
@@ -12,6 +19,9 @@ pub type ObjectPosition = usize;
 /// The identity of a Spawn
 pub type SpawnId = usize;
 
+/// The identity of a spawn group
+pub type GroupId = usize;
+
 /// A callback handler used by the for_each() methode on Swarm.
 /// Return a mutable reference of a data object in the pool that 
 /// the for_each() loop is currently iterating over.
@@ -23,33 +33,181 @@ pub type ForEachHandler<ItemType> = fn(&mut ItemType);
 pub type EnumerateHandler<ItemType> = fn(&usize, &mut ItemType);
 
 /// A callback handler used by the for_all() methode on Swarm.
-/// Returns the object position, a mutable pool reference and the swarm properties  
+/// Returns the object position, a mutable slice of the spawned objects and the swarm properties
 /// the for_all() loop is currently iterating over.
 pub type ForAllHandler<ItemType, Properties> = fn(&ObjectPosition, &mut [ItemType], &mut Properties);
 
+/// A callback handler used by the for_each_chunk() methode on Swarm.
+/// Returns a mutable slice of at most the requested number of spawned objects.
+pub type ChunkHandler<ItemType> = fn(&mut [ItemType]);
+
+/// A callback handler used by the for_all_buffered() methode on Swarm.
+/// Returns the object position, the pool as it was before the loop started, a mutable 
+/// reference to the next version of the object at that position and the swarm properties.
+pub type BufferedHandler<ItemType, Properties> = fn(&ObjectPosition, &[ItemType], &mut ItemType, &mut Properties);
+
 /// A callback handler used by the update() methode on Swarm.
 /// Return a SwarmControl object that refers to the object the update() loop 
 /// is currently iterating over.
+#[cfg(feature = "alloc")]
 pub type UpdateHandler<ItemType, Properties> = fn(&mut SwarmControl<ItemType, Properties>);
 
+/// A callback handler used by the update() methode on StaticSwarm.
+/// Return a StaticControl object that refers to the object the update() loop 
+/// is currently iterating over.
+pub type StaticUpdateHandler<ItemType, Properties, const N: usize> = fn(&mut StaticControl<ItemType, Properties, N>);
+
 pub type FactoryHandler<ItemType, Properties> = fn(&mut ItemType, &mut Properties);
 
+/// A callback handler used by Swarm to construct a pool object the first time its
+/// slot is spawned, see Swarm::new_with().
+pub type ConstructHandler<ItemType> = fn() -> ItemType;
+
+/// A callback handler used by Swarm to encode the values passed to spawn_with() and 
+/// populate() into a recording, see Swarm::start_recording_with().
+#[cfg(feature = "alloc")]
+pub type EncodeHandler<ItemType> = fn(&ItemType, &mut Vec<u8>);
+
+/// A callback handler used by Swarm to decode the values of a recording, see Swarm::replay_with().
+/// Returns None if the bytes are not a valid value.
+pub type DecodeHandler<ItemType> = fn(&[u8]) -> Option<ItemType>;
+
+/// A callback handler used by the tick() methode on Swarm.
+/// Returns the pool object of a spawn whose lifetime expired, before it is killed and recycled.
+pub type ExpireHandler<ItemType, Properties> = fn(&mut ItemType, &mut Properties);
+
 
 pub struct Factory<ItemType, Properties> {
     pub type_def: usize,
     pub methode: FactoryHandler<ItemType, Properties>,
 }
 
+/// Usage statistics of a swarm, see Swarm.stats()
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct SwarmStats {
+    /// The number of successful spawns
+    pub spawns: usize,
+    /// The number of kills, including expired spawns and kill_all()
+    pub kills: usize,
+    /// The number of currently spawned instances
+    pub len: usize,
+    /// The highest number of spawned instances at the same time
+    pub peak_len: usize,
+    /// The number of spawns that failed because the pool was full
+    pub failed_spawns: usize,
+    /// The number of killed spawns that are waiting to be recycled
+    pub free_len: usize,
+    /// The average number of ticks killed spawns lived, see Swarm.tick()
+    pub average_lifetime: f64,
+}
+
+// the running totals Swarm keeps to compute its SwarmStats
+#[cfg(feature = "alloc")]
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Counters {
+    pub(crate) spawns: usize,
+    pub(crate) kills: usize,
+    pub(crate) peak_len: usize,
+    pub(crate) failed_spawns: usize,
+    pub(crate) lifetimes: usize,    // the sum of the lifetimes of all killed spawns
+}
+
+/// A broken invariant of a swarm, found by Swarm.check_integrity()
+#[cfg(feature = "alloc")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// The number of spawned instances exceeds the capacity
+    Overflow { len: usize, max: usize },
+    /// One of the spawn administration buffers does not hold an entry per slot
+    BufferSize { name: &'static str, size: usize, max: usize },
+    /// The number of constructed pool objects does not match the number of used slots
+    Constructed { constructed: usize, used: usize },
+    /// The spawn at a pool position points to another position
+    Position { pos: ObjectPosition, points_to: ObjectPosition },
+    /// The spawn at a pool position is not the spawn known by its id
+    Identity { pos: ObjectPosition, id: SpawnId },
+    /// A spawn inside the spawned range is inactive, or one outside of it is active
+    Active { pos: ObjectPosition, active: bool },
+    /// The free list does not hold the killed spawns that follow the spawned range
+    Free { index: usize, pos: ObjectPosition },
+    /// A group holds a spawn that is inactive or not part of the swarm
+    Group { group_id: GroupId, id: SpawnId },
+    /// The number of groups a spawn is counted in differs from the groups that hold it
+    Membership { id: SpawnId, counted: u32, found: u32 },
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityError::Overflow { len, max } => 
+                write!(f, "{} spawned instances exceed the capacity of {}", len, max),
+            IntegrityError::BufferSize { name, size, max } => 
+                write!(f, "the {} buffer holds {} entries instead of {}", name, size, max),
+            IntegrityError::Constructed { constructed, used } => 
+                write!(f, "{} pool objects are constructed for {} used slots", constructed, used),
+            IntegrityError::Position { pos, points_to } => 
+                write!(f, "the spawn at position {} points to position {}", pos, points_to),
+            IntegrityError::Identity { pos, id } => 
+                write!(f, "the spawn at position {} is not the spawn with id {}", pos, id),
+            IntegrityError::Active { pos, active } => 
+                write!(f, "the spawn at position {} is unexpectedly {}", pos, if *active { "active" } else { "inactive" }),
+            IntegrityError::Free { index, pos } => 
+                write!(f, "free list entry {} is not the killed spawn at position {}", index, pos),
+            IntegrityError::Group { group_id, id } => 
+                write!(f, "group {} holds the dead or foreign spawn {}", group_id, id),
+            IntegrityError::Membership { id, counted, found } => 
+                write!(f, "spawn {} is counted in {} groups, but {} groups hold it", id, counted, found),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IntegrityError {}
+
+/// Returned by Swarm.shrink_to() when spawned instances do not fit the requested capacity.
+/// Spawned instances are not relocated and keep their id, so a swarm cannot shrink below 
+/// its highest spawned id. A single spawned instance with a high id is enough to block
+/// shrinking, `min_capacity` tells how far the swarm can shrink while it is alive.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ShrinkError {
+    /// The requested capacity
+    pub capacity: usize,
+    /// The smallest capacity the swarm can currently shrink to
+    pub min_capacity: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for ShrinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot shrink to {} slots, the spawned instances need at least {}", self.capacity, self.min_capacity)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ShrinkError {}
+
+#[cfg(feature = "alloc")]
+/// A group holds a subset of spawns that can be iterated over without having to 
+/// loop through the entire pool, for example "all enemies" or "all projectiles".
+pub struct Group {
+    pub group_id: GroupId,
+    pub(crate) members: Vec<Spawn>,
+}
+
 // spawns and tags
 
 /// A spawn is a pointer that points to a data object in the swarm pool.
 /// Spawns are 'Reference Counted' which makes it possible to hand them out 
 /// like free candy during halloween, no (compiler) questions asked ;)
+#[cfg(feature = "alloc")]
 pub struct Spawn(pub(crate) Rc<RefCell<Tag>>);
 
+#[cfg(feature = "alloc")]
 impl Spawn {
     pub(crate) fn new(index: usize) -> Self {
-        Spawn( Rc::new( RefCell::new( Tag{ id:index, pos:index, active:false })))
+        Spawn( Rc::new( RefCell::new( Tag{ id:index, pos:index, active:false, pending:false, ttl:None, born:0, gen:0, changed:None, died:None, groups:0 })))
     }
 
     /// Returns the identity of this Spawns. All RC clones of this spawn have the same 'id' 
@@ -68,12 +226,121 @@ impl Spawn {
         self.0.borrow().active 
     }
 
+    /// Returns the number of ticks this spawn has left to live, or None if it lives forever
+    pub fn ttl(&self) -> Option<usize> { 
+        self.0.borrow().ttl 
+    }
+
     /// Returns a 'Reference Counted' clone of this Spawn
     pub fn mirror(&self) -> Self { 
         Spawn (Rc::clone(&self.0)) 
     }
+
+    /// Returns a WeakSpawn that observes this spawn without owning it, see WeakSpawn.
+    /// The WeakSpawn of a spawn that was already killed never upgrades.
+    pub fn downgrade(&self) -> WeakSpawn {
+        if self.active() {
+            WeakSpawn { tag: Rc::downgrade(&self.0), gen: self.gen() }
+        } else {
+            WeakSpawn { tag: Weak::new(), gen: 0 }
+        }
+    }
+
+    /// Encodes this spawn as a number that can cross FFI, network or save-file boundaries.
+    /// The lower 32 bits hold the id, the upper 32 bits the number of times the spawn was
+    /// killed, so a number of a killed spawn is not accepted by `from_bits()` anymore.
+    /// Spawns that are already killed encode the generation they were killed in, so their number
+    /// is never accepted, not even after their slot is spawned again.
+    pub fn to_bits(&self) -> u64 {
+        let tag = self.0.borrow();
+        let gen = if tag.active { tag.gen } else { tag.gen.wrapping_sub(1) };
+        (gen as u64) << 32 | (tag.id as u64 & 0xFFFF_FFFF)
+    }
+
+    /// Returns the spawn a number created by `to_bits()` refers to in the `swarm`.
+    /// Returns None if the id is unknown or if the spawn was killed since it was encoded.
+    /// 
+    /// # Example
+    /// ```
+    /// extern crate swarm_pool;
+    /// use swarm_pool::{ Swarm, Spawn };
+    /// 
+    /// let mut swarm = Swarm::<u8, _>::new(10, ());
+    /// let spawn = swarm.spawn().unwrap();
+    /// let bits = spawn.to_bits();
+    /// assert_eq!(Spawn::from_bits(bits, &swarm), Some(spawn.mirror()));
+    /// 
+    /// swarm.kill(&spawn);
+    /// swarm.spawn();
+    /// assert_eq!(Spawn::from_bits(bits, &swarm), None);
+    /// ```
+    pub fn from_bits<ItemType, Properties>(bits: u64, swarm: &Swarm<ItemType, Properties>) -> Option<Spawn> {
+        let id = (bits & 0xFFFF_FFFF) as SpawnId;
+        let gen = (bits >> 32) as u32;
+
+        swarm.by_id.get(id)
+            .filter(|s| s.active() && s.gen() == gen)
+            .map(Spawn::mirror)
+    }
+
+    pub(crate) fn gen(&self) -> u32 {
+        self.0.borrow().gen
+    }
 }
 
+/// A weak pointer to a spawned pool object, created by `Spawn.downgrade()`.
+/// 
+/// Where a Spawn keeps pointing to its slot whatever happens to it, a WeakSpawn only 
+/// upgrades to a Spawn while the object it was created from is alive. Once that object
+/// is killed, upgrade() returns None, even if its slot is spawned again later on.
+/// This makes WeakSpawns a safe way to refer to AI or camera targets.
+/// 
+/// # Example
+/// ```
+/// extern crate swarm_pool;
+/// use swarm_pool::Swarm;
+/// 
+/// let mut swarm = Swarm::<u8, _>::new(1, ());
+/// let orc = swarm.spawn().unwrap();
+/// let target = orc.downgrade();
+/// assert!(target.upgrade(&swarm).is_some());
+/// 
+/// // the slot is reused by a goblin, but the target is gone
+/// swarm.kill(&orc);
+/// let goblin = swarm.spawn().unwrap();
+/// assert_eq!(goblin.id(), orc.id());
+/// assert!(target.upgrade(&swarm).is_none());
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct WeakSpawn {
+    tag: Weak<RefCell<Tag>>,
+    gen: u32,   // the generation of the spawn when it was downgraded
+}
+
+#[cfg(feature = "alloc")]
+impl WeakSpawn {
+    /// Returns the Spawn if the object it points to is still alive in the `swarm`.
+    /// Returns None if it was killed, or if the spawn belongs to another swarm.
+    pub fn upgrade<ItemType, Properties>(&self, swarm: &Swarm<ItemType, Properties>) -> Option<Spawn> {
+        self.upgrade_in(&swarm.spawns[..swarm.len])
+    }
+
+    // upgrades if the spawn is alive and one of the `active` spawns of its swarm
+    pub(crate) fn upgrade_in(&self, active: &[Spawn]) -> Option<Spawn> {
+        let rc = self.tag.upgrade()?;
+        let tag = rc.borrow();
+
+        if tag.active && tag.gen == self.gen && 
+            active.get(tag.pos).is_some_and(|s| Rc::ptr_eq(&s.0, &rc)) {
+            Some(Spawn(Rc::clone(&rc)))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl fmt::Debug for Spawn {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Debug::fmt(&self.0, f)
@@ -81,15 +348,44 @@ impl fmt::Debug for Spawn {
 }
 
 /// Spawns are compared by their identity or 'id' value
+#[cfg(feature = "alloc")]
 impl PartialEq for Spawn {
     fn eq(&self, other: &Spawn) -> bool {
         self.id() == other.id()
     }
 }
 
+#[cfg(feature = "alloc")]
+impl Eq for Spawn {}
+
+/// Spawns are hashed by their identity, which never changes, so they can be used as map keys.
+/// Clippy's `mutable_key_type` lint does not know this and can be allowed for spawn keys.
+#[cfg(feature = "alloc")]
+impl Hash for Spawn {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state)
+    }
+}
+
+/// Spawns are ordered by their identity or 'id' value
+#[cfg(feature = "alloc")]
+impl PartialOrd for Spawn {
+    fn partial_cmp(&self, other: &Spawn) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Ord for Spawn {
+    fn cmp(&self, other: &Spawn) -> Ordering {
+        self.id().cmp(&other.id())
+    }
+}
+
 /// Implements default so it can be used as a property in PoolObjects.
 /// A Spawn shouls always point to an object, so in reality Spawn cannot have a default value.
 /// Allthough it is implemented default should NOT be used.
+#[cfg(feature = "alloc")]
 impl Default for Spawn {
     fn default() -> Self {
         Spawn( Rc::new( RefCell::new( Tag::default() )))
@@ -100,18 +396,148 @@ impl Default for Spawn {
 /// Allthough this makes the mirror function obsolete, support for mirror shall be contiued. 
 /// This because the name mirror tells use that there is more going on than just cloning 
 /// (in this case Reference Counting). 
+#[cfg(feature = "alloc")]
 impl Clone for Spawn {
     fn clone(&self) -> Self {
         self.mirror()
     }
 }
 
+/// A snapshot of the full state of a swarm, see Swarm.save_state()
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub struct SwarmState<ItemType, Properties> {
+    pub(crate) pool: Vec<ItemType>,
+    pub(crate) tags: Vec<Tag>,              // per spawn id, the state of its spawn
+    pub(crate) spawns: Vec<SpawnId>,        // per object position, the id of its spawn
+    pub(crate) free: Vec<SpawnId>,
+    pub(crate) groups: Vec<(GroupId, usize)>,   // the group ids and their number of members
+    pub(crate) members: Vec<SpawnId>,           // the members of all groups, in group order
+    pub(crate) len: usize,
+    pub(crate) counters: Counters,
+    pub(crate) ticks: usize,
+    pub(crate) properties: Properties,
+}
+
+#[cfg(feature = "alloc")]
+impl<ItemType, Properties> SwarmState<ItemType, Properties> {
+    /// Returns the number of spawned instances at the time the state was saved
+    pub fn count(&self) -> usize { self.len }
+
+    /// Returns the tick the state was saved at, see Swarm.ticks()
+    pub fn ticks(&self) -> usize { self.ticks }
+}
+
+/// Translates the spawns of a swarm to the matching spawns of its clone, see Swarm.clone_with_map().
+/// Use it to update spawns that are stored in the properties or pool objects of the clone.
+#[cfg(feature = "alloc")]
+pub struct SpawnMap {
+    pub(crate) from: Vec<Weak<RefCell<Tag>>>,  // per spawn id, the spawn of the original swarm
+    pub(crate) to: Vec<Spawn>,                 // per spawn id, the spawn of the clone
+}
+
+#[cfg(feature = "alloc")]
+impl SpawnMap {
+    /// Returns the spawn of the clone that matches a `spawn` of the original swarm.
+    /// Returns None for spawns that do not belong to the original swarm.
+    pub fn translate(&self, spawn: &Spawn) -> Option<Spawn> {
+        let id = spawn.id();
+        match self.from.get(id) {
+            Some(from) if core::ptr::eq(from.as_ptr(), Rc::as_ptr(&spawn.0)) => Some(self.to[id].mirror()),
+            _ => None,
+        }
+    }
+
+    /// Returns the WeakSpawn of the clone that matches a `weak` spawn of the original swarm,
+    /// the clone's WeakSpawn only upgrades if the original one would.
+    pub fn translate_weak(&self, weak: &WeakSpawn) -> Option<WeakSpawn> {
+        let spawn = Spawn(weak.tag.upgrade()?);
+        let mut translated = self.translate(&spawn)?.downgrade();
+        translated.gen = weak.gen;
+        Some(translated)
+    }
+}
+
+// the spawns of dropped SpawnGuards and the generation they were spawned with,
+// waiting for the swarm to kill them
+#[cfg(feature = "alloc")]
+pub(crate) type KillQueue = Rc<RefCell<Vec<(Spawn, u32)>>>;
+
+/// A spawn that kills its pool object when the guard is dropped, see Swarm.spawn_scoped().
+/// The guard dereferences to its Spawn, so it can be passed to fetch() and friends.
+/// 
+/// Dropping a guard does not need access to the swarm, the kill is queued and executed
+/// the next time the swarm spawns, kills, ticks or updates, or when `Swarm.kill_dropped()`
+/// is called. Objects that were killed, or recycled, before the guard was dropped are left alone.
+#[cfg(feature = "alloc")]
+pub struct SpawnGuard {
+    spawn: Spawn,
+    gen: u32,
+    armed: bool,
+    queue: KillQueue,
+}
+
+#[cfg(feature = "alloc")]
+impl SpawnGuard {
+    pub(crate) fn new(spawn: Spawn, queue: KillQueue) -> Self {
+        SpawnGuard { gen: spawn.gen(), spawn, armed: true, queue }
+    }
+
+    /// Returns the spawn this guard points to
+    pub fn spawn(&self) -> &Spawn { 
+        &self.spawn 
+    }
+
+    /// Disarms the guard and returns a plain Spawn, the object lives on until it is killed
+    pub fn into_inner(mut self) -> Spawn {
+        self.armed = false;
+        self.spawn.mirror()
+    }
+
+    /// Disarms the guard without keeping a handle, the object lives on until it is killed
+    pub fn forget(mut self) {
+        self.armed = false;
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::ops::Deref for SpawnGuard {
+    type Target = Spawn;
+
+    fn deref(&self) -> &Spawn { 
+        &self.spawn 
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Drop for SpawnGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            self.queue.borrow_mut().push((self.spawn.mirror(), self.gen));
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for SpawnGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpawnGuard").field("spawn", &self.spawn).field("armed", &self.armed).finish()
+    }
+}
+
 /// Tags hold Spawn data, and since A spawn is a Refence Counted Tag, that makes a Tag kind of an abstract Spawn
 #[derive(Default, Debug, Copy, Clone, PartialEq)]
 pub struct Tag {
     pub(crate) id: SpawnId,
     pub(crate) pos: ObjectPosition,
     pub(crate) active: bool,
+    pub(crate) pending: bool,   // true while update() still has to visit this spawn
+    pub(crate) ttl: Option<usize>,
+    pub(crate) born: usize,     // the tick this spawn was spawned at
+    pub(crate) gen: u32,        // incremented every time this spawn is killed
+    pub(crate) changed: Option<usize>,  // the tick its object was last accessed mutably, see Swarm.track_changes()
+    pub(crate) died: Option<usize>,     // the tick this spawn was last killed at
+    pub(crate) groups: u32,             // the number of groups this spawn is a member of
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,213 @@
+//! A World holds several swarms of different pool object types.
+//!
+//! Real games need more than one swarm, for example units, projectiles and effects.
+//! The World owns these swarms and looks them up by their type, TypedSpawns can be
+//! used to refer to objects in another swarm, and `World.update()` lets a system
+//! borrow two swarms at once.
+//!
+//! Example
+//! ```
+//! extern crate swarm_pool;
+//! use swarm_pool::Swarm;
+//! use swarm_pool::world::{ World, TypedSpawn };
+//!
+//! #[derive(Default, Clone)]
+//! pub struct Unit { health: usize }
+//!
+//! #[derive(Default, Clone)]
+//! pub struct Projectile { damage: usize, target: TypedSpawn<Unit, ()> }
+//!
+//! let mut world = World::new();
+//! world.insert(Swarm::<Unit, _>::new(10, ()));
+//! world.insert(Swarm::<Projectile, _>::new(10, ()));
+//!
+//! let unit = world.spawn::<Unit, ()>().unwrap();
+//! world.fetch(&unit).unwrap().health = 10;
+//!
+//! let arrow = world.spawn::<Projectile, ()>().unwrap();
+//! *world.fetch(&arrow).unwrap() = Projectile { damage: 3, target: unit.mirror() };
+//!
+//! // projectiles hit their targets
+//! world.update(|projectiles: &mut Swarm<Projectile, ()>, units: &mut Swarm<Unit, ()>| {
+//!     for pos in 0..projectiles.count() {
+//!         let projectile = projectiles.fetch_raw(&pos).clone();
+//!         units.fetch(projectile.target.spawn()).health -= projectile.damage;
+//!     }
+//! }).unwrap();
+//!
+//! assert_eq!(world.fetch(&unit).unwrap().health, 7);
+//! ```
+
+use std::any::{ Any, TypeId, type_name };
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::fmt;
+
+use super::{ Swarm, Spawn };
+
+/// Holds several swarms of different types, one swarm per combination of pool object
+/// and properties type.
+#[derive(Default)]
+pub struct World {
+    swarms: HashMap<TypeId, Box<dyn Any>>,    // keyed by the TypeId of the Swarm
+}
+
+impl World {
+
+    /// Create a new, empty, World
+    pub fn new() -> Self {
+        World { swarms: HashMap::new() }
+    }
+
+    /// Add a swarm to the world. A world holds one swarm per swarm type, if the world 
+    /// already held a `Swarm<ItemType, Properties>`, the old swarm is returned.
+    /// Swarms of the same pool object type with different properties are kept apart.
+    pub fn insert<ItemType, Properties>(&mut self, swarm: Swarm<ItemType, Properties>) -> Option<Swarm<ItemType, Properties>>
+    where ItemType: 'static, Properties: 'static {
+        self.swarms
+            .insert(key::<ItemType, Properties>(), Box::new(swarm))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    /// Remove a swarm from the world and return it
+    pub fn remove<ItemType, Properties>(&mut self) -> Option<Swarm<ItemType, Properties>>
+    where ItemType: 'static, Properties: 'static {
+        self.swarms
+            .remove(&key::<ItemType, Properties>())
+            .and_then(|s| s.downcast().ok())
+            .map(|s| *s)
+    }
+
+    /// Returns true if the world holds a `Swarm<ItemType, Properties>`
+    pub fn contains<ItemType, Properties>(&self) -> bool
+    where ItemType: 'static, Properties: 'static {
+        self.swarms.contains_key(&key::<ItemType, Properties>())
+    }
+
+    /// Returns the number of swarms in this world
+    pub fn count(&self) -> usize { self.swarms.len() }
+
+    /// Returns a reference to the swarm that holds pool objects of type `ItemType`
+    pub fn get<ItemType, Properties>(&self) -> Option<&Swarm<ItemType, Properties>>
+    where ItemType: 'static, Properties: 'static {
+        self.swarms
+            .get(&key::<ItemType, Properties>())
+            .and_then(|s| s.downcast_ref())
+    }
+
+    /// Returns a mutable reference to the swarm that holds pool objects of type `ItemType`
+    pub fn get_mut<ItemType, Properties>(&mut self) -> Option<&mut Swarm<ItemType, Properties>>
+    where ItemType: 'static, Properties: 'static {
+        self.swarms
+            .get_mut(&key::<ItemType, Properties>())
+            .and_then(|s| s.downcast_mut())
+    }
+
+    /// Create a new pool instance in the swarm that holds pool objects of type `ItemType`.
+    /// Returns None if there is no such swarm, or if it reached its maximum capacity.
+    pub fn spawn<ItemType, Properties>(&mut self) -> Option<TypedSpawn<ItemType, Properties>>
+    where ItemType: 'static, Properties: 'static {
+        self.get_mut::<ItemType, Properties>()
+            .and_then(|swarm| swarm.spawn())
+            .map(TypedSpawn::new)
+    }
+
+    /// Remove a spawn instance from its swarm update loops
+    pub fn kill<ItemType, Properties>(&mut self, spawn: &TypedSpawn<ItemType, Properties>)
+    where ItemType: 'static, Properties: 'static {
+        if let Some(swarm) = self.get_mut::<ItemType, Properties>() {
+            swarm.kill(&spawn.spawn);
+        }
+    }
+
+    /// Returns a mutable reference to the pool object a TypedSpawn points to,
+    /// or None if the world does not hold a swarm for its type.
+    pub fn fetch<ItemType, Properties>(&mut self, spawn: &TypedSpawn<ItemType, Properties>) -> Option<&mut ItemType>
+    where ItemType: 'static, Properties: 'static {
+        self.get_mut::<ItemType, Properties>()
+            .map(|swarm| swarm.fetch(&spawn.spawn))
+    }
+
+    /// Returns a immutable reference to the pool object a TypedSpawn points to,
+    /// or None if the world does not hold a swarm for its type.
+    pub fn fetch_ref<ItemType, Properties>(&self, spawn: &TypedSpawn<ItemType, Properties>) -> Option<&ItemType>
+    where ItemType: 'static, Properties: 'static {
+        self.get::<ItemType, Properties>()
+            .map(|swarm| swarm.fetch_ref(&spawn.spawn))
+    }
+
+    /// Run a system that borrows two different swarms at once, for example to let
+    /// projectiles hit units, and return its result.
+    ///
+    /// Returns None, without running the system, if the world does not hold both swarms.
+    ///
+    /// # Panics
+    /// If both swarms are the same swarm type.
+    pub fn update<A, PA, B, PB, R>(&mut self, system: fn(&mut Swarm<A, PA>, &mut Swarm<B, PB>) -> R) -> Option<R>
+    where A: 'static, PA: 'static, B: 'static, PB: 'static {
+        let (key_a, key_b) = (key::<A, PA>(), key::<B, PB>());
+        assert!(key_a != key_b,
+            "World.update() cannot borrow the Swarm<{}, {}> twice", type_name::<A>(), type_name::<PA>());
+
+        // both swarms are looked up before either one is moved out
+        if !self.swarms.contains_key(&key_a) || !self.swarms.contains_key(&key_b) {
+            return None;
+        }
+        let mut a = self.swarms.remove(&key_a)?;
+        let mut b = self.swarms.remove(&key_b)?;
+        let result = match (a.downcast_mut(), b.downcast_mut()) {
+            (Some(a), Some(b)) => Some(system(a, b)),
+            _ => None,
+        };
+
+        self.swarms.insert(key_a, a);
+        self.swarms.insert(key_b, b);
+        result
+    }
+}
+
+// the key of a swarm type in the world
+fn key<ItemType: 'static, Properties: 'static>() -> TypeId {
+    TypeId::of::<Swarm<ItemType, Properties>>()
+}
+
+/// A Spawn that also knows what type of swarm it belongs to. TypedSpawns can be stored
+/// inside the pool objects or properties of one swarm to refer to objects of another swarm.
+pub struct TypedSpawn<ItemType, Properties> {
+    spawn: Spawn,
+    swarm_type: PhantomData<fn() -> (ItemType, Properties)>,
+}
+
+impl<ItemType, Properties> TypedSpawn<ItemType, Properties> {
+
+    /// Wrap a Spawn, the spawn should belong to a `Swarm<ItemType, Properties>`
+    pub fn new(spawn: Spawn) -> Self {
+        TypedSpawn { spawn, swarm_type: PhantomData }
+    }
+
+    /// Returns the untyped Spawn, which can be used on the swarm directly
+    pub fn spawn(&self) -> &Spawn { &self.spawn }
+
+    /// Returns a 'Reference Counted' clone of this TypedSpawn, see Spawn.mirror()
+    pub fn mirror(&self) -> Self { TypedSpawn::new(self.spawn.mirror()) }
+}
+
+impl<ItemType, Properties> Clone for TypedSpawn<ItemType, Properties> {
+    fn clone(&self) -> Self { self.mirror() }
+}
+
+/// Implements default so it can be used as a property in PoolObjects, see Spawn.default()
+impl<ItemType, Properties> Default for TypedSpawn<ItemType, Properties> {
+    fn default() -> Self { TypedSpawn::new(Spawn::default()) }
+}
+
+impl<ItemType, Properties> PartialEq for TypedSpawn<ItemType, Properties> {
+    fn eq(&self, other: &Self) -> bool { self.spawn == other.spawn }
+}
+
+impl<ItemType, Properties> fmt::Debug for TypedSpawn<ItemType, Properties> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.spawn, f)
+    }
+}